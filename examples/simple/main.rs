@@ -1,9 +1,10 @@
 use chrono::prelude::*;
 use dgraph::{Dgraph, make_dgraph};
+use dgraph::query::{Arg, Block, Edge, Func, Query, Vars};
+use dgraph::schema::{Predicate, ScalarType, Schema, Tokenizer};
 use serde_derive::{Serialize, Deserialize};
 use slog::{Drain, slog_info, slog_o};
 use slog_scope::{info};
-use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Root {
@@ -15,13 +16,6 @@ pub struct School {
 	pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-pub struct Location {
-	#[serde(rename = "type")]
-	pub kind: String,
-	pub coordinates: Vec<f64>,
-}
-
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Person {
 	pub name: String,
@@ -29,7 +23,7 @@ pub struct Person {
 	pub dob: Option<DateTime<Utc>>,
 	pub married: Option<bool>,
 	pub friend: Option<Vec<Person>>,
-	pub loc: Option<Location>,
+	pub loc: Option<dgraph::Point>,
 	pub school: Option<Vec<School>>,
 }
 
@@ -43,22 +37,20 @@ fn drop_all(dgraph: &Dgraph) {
 }
 
 fn set_schema(dgraph: &Dgraph) {
-    let op_schema = dgraph::Operation {
-        schema: r#"
-            name: string @index(exact) .
-            age: int .
-            married: bool .
-            loc: geo .
-            dob: datetime .
-        "#.to_string(),
-        ..Default::default()
-    };
+    let op_schema = Schema::new()
+        .predicate(Predicate::new("name", ScalarType::String).index(&[Tokenizer::Exact]))
+        .predicate(Predicate::new("age", ScalarType::Int))
+        .predicate(Predicate::new("married", ScalarType::Bool))
+        .predicate(Predicate::new("loc", ScalarType::Geo))
+        .predicate(Predicate::new("dob", ScalarType::DateTime))
+        .operation()
+        .expect("valid schema");
 
     dgraph.alter(&op_schema).expect("set schema");
 }
 
 fn create_data(dgraph: &Dgraph) {
-    let mut txn = dgraph.new_txn();
+    let mut txn = dgraph.new_txn().expect("txn");
 
     let dob = Utc.ymd(1980, 1, 1).and_hms(23, 0, 0);
     // While setting an object if a struct has a Uid then its properties in the graph are updated
@@ -69,10 +61,7 @@ fn create_data(dgraph: &Dgraph) {
         name: "Alice".to_string(),
         age: Some(26),
         married: Some(true),
-        loc: Some(Location {
-            kind: "Point".to_string(),
-            coordinates: vec![1.1f64, 2f64],
-        }),
+        loc: Some(dgraph::Point::new(1.1, 2.0)),
         dob: Some(dob),
         friend: Some(vec![
             Person {
@@ -94,49 +83,42 @@ fn create_data(dgraph: &Dgraph) {
     };
 
     // Run mutation
-    let mut mutation = dgraph::Mutation::new(); 
-    mutation.set_set_json(serde_json::to_vec(&p).expect("invalid json"));
-    let assigned = txn.mutate(mutation).expect("failed to create data");
+    let uids = txn.set_json(&p).expect("failed to create data");
 
     // Commit transaction
     txn.commit().expect("Fail to commit mutation");
 
     // Get uid of the outermost object (person named "Alice").
-    // Assigned#getUidsMap() returns a map from blank node names to uids.
-    // For a json mutation, blank node names "blank-0", "blank-1", ... are used
-    // for all the created nodes.
-    info!("Created person named 'Alice' with uid = {}", assigned.uids["blank-0"]);
+    // Txn#set_json() returns the uids assigned to blank nodes. For a json mutation,
+    // blank node names "blank-0", "blank-1", ... are used for all the created nodes,
+    // and AssignedUids#json_uids() resolves them back to the submitted objects.
+    let alice = uids.json_uids(&p).expect("invalid json")[0].expect("uid of Alice");
+    info!("Created person named 'Alice' with uid = {}", alice);
 
     info!("All created nodes (map from blank node names to uids):");
-    for (key, val) in assigned.uids.iter() {
+    for (key, val) in uids.iter() {
         info!("\t{} => {}", key, val);
     }
 }
 
 fn query_data(dgraph: &Dgraph) {
-    let query = r#"query all($a: string){
-        me(func: eq(name, $a)) {
-            name
-            age
-            married
-            loc
-            dob
-            friend {
-                name
-                age
-            }
-            school {
-                name
-            }
-        }
-    }"#.to_string();
-
-    let mut vars = HashMap::new();
-    vars.insert("$a".to_string(), "Alice".to_string());
-
-    let resp = dgraph.new_readonly_txn().query_with_vars(query, vars).expect("query");
-    let root: Root = serde_json::from_slice(&resp.json).expect("parsing");
-    info!("Root: {:#?}", root);
+    let vars = Vars::new().set("a", "Alice");
+
+    let query = Query::named("all")
+        .vars(&vars)
+        .block(
+            Block::new("me", Func::eq("name", Arg::var("a")))
+                .field("name")
+                .field("age")
+                .field("married")
+                .field("loc")
+                .field("dob")
+                .edge(Edge::new("friend").field("name").field("age"))
+                .edge(Edge::new("school").field("name")),
+        );
+
+    let resp = dgraph.new_readonly_txn().expect("txn").query_with_typed_vars_as::<Root>(&query, &vars).expect("query");
+    info!("Root: {:#?}", resp.data);
 }
 
 fn run_example() {
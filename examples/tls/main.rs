@@ -19,13 +19,6 @@ pub struct School {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-pub struct Location {
-    #[serde(rename = "type")]
-    pub kind: String,
-    pub coordinates: Vec<f64>,
-}
-
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Person {
     pub name: String,
@@ -33,7 +26,7 @@ pub struct Person {
     pub dob: Option<DateTime<Utc>>,
     pub married: Option<bool>,
     pub friend: Option<Vec<Person>>,
-    pub loc: Option<Location>,
+    pub loc: Option<dgraph::Point>,
     pub school: Option<Vec<School>>,
 }
 
@@ -63,7 +56,7 @@ fn set_schema(dgraph: &Dgraph) {
 }
 
 fn create_data(dgraph: &Dgraph) {
-    let mut txn = dgraph.new_txn();
+    let mut txn = dgraph.new_txn().expect("txn");
 
     let dob = Utc.ymd(1980, 1, 1).and_hms(23, 0, 0);
     // While setting an object if a struct has a Uid then its properties in the graph are updated
@@ -74,10 +67,7 @@ fn create_data(dgraph: &Dgraph) {
         name: "Alice".to_string(),
         age: Some(26),
         married: Some(true),
-        loc: Some(Location {
-            kind: "Point".to_string(),
-            coordinates: vec![1.1f64, 2f64],
-        }),
+        loc: Some(dgraph::Point::new(1.1, 2.0)),
         dob: Some(dob),
         friend: Some(vec![
             Person {
@@ -97,24 +87,20 @@ fn create_data(dgraph: &Dgraph) {
     };
 
     // Run mutation
-    let mut mutation = dgraph::Mutation::new();
-    mutation.set_set_json(serde_json::to_vec(&p).expect("invalid json"));
-    let assigned = txn.mutate(mutation).expect("failed to create data");
+    let uids = txn.set_json(&p).expect("failed to create data");
 
     // Commit transaction
     txn.commit().expect("Fail to commit mutation");
 
     // Get uid of the outermost object (person named "Alice").
-    // Assigned#getUidsMap() returns a map from blank node names to uids.
-    // For a json mutation, blank node names "blank-0", "blank-1", ... are used
-    // for all the created nodes.
-    info!(
-        "Created person named 'Alice' with uid = {}",
-        assigned.uids["blank-0"]
-    );
+    // Txn#set_json() returns the uids assigned to blank nodes. For a json mutation,
+    // blank node names "blank-0", "blank-1", ... are used for all the created nodes,
+    // and AssignedUids#json_uids() resolves them back to the submitted objects.
+    let alice = uids.json_uids(&p).expect("invalid json")[0].expect("uid of Alice");
+    info!("Created person named 'Alice' with uid = {}", alice);
 
     info!("All created nodes (map from blank node names to uids):");
-    for (key, val) in assigned.uids.iter() {
+    for (key, val) in uids.iter() {
         info!("\t{} => {}", key, val);
     }
 }
@@ -143,10 +129,10 @@ fn query_data(dgraph: &Dgraph) {
 
     let resp = dgraph
         .new_readonly_txn()
-        .query_with_vars(query, vars)
+        .expect("txn")
+        .query_with_vars_as::<Root>(query, vars)
         .expect("query");
-    let root: Root = serde_json::from_slice(&resp.json).expect("parsing");
-    info!("Root: {:#?}", root);
+    info!("Root: {:#?}", resp.data);
 }
 
 fn open_cert_file(path: &str) -> Vec<u8> {
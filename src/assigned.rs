@@ -0,0 +1,73 @@
+use std::collections::hash_map;
+use std::collections::HashMap;
+
+use crate::errors::DgraphError;
+use crate::protos::api;
+use crate::uid::Uid;
+
+/// Uids assigned by a mutation, keyed by the name of the blank node they were
+/// assigned to. Blank nodes of JSON mutations without an explicit uid are named
+/// `blank-0`, `blank-1`, ... by the server.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssignedUids {
+    uids: HashMap<String, Uid>,
+}
+
+impl AssignedUids {
+    pub fn from_assigned(assigned: &api::Assigned) -> Result<AssignedUids, DgraphError> {
+        let uids = assigned
+            .uids
+            .iter()
+            .map(|(name, uid)| Ok((name.clone(), uid.parse()?)))
+            .collect::<Result<_, DgraphError>>()?;
+
+        Ok(AssignedUids { uids })
+    }
+
+    /// Returns the uid assigned to the blank node. The name can be given with or
+    /// without the `_:` prefix.
+    pub fn get(&self, name: &str) -> Option<Uid> {
+        let name = if name.starts_with("_:") { &name[2..] } else { name };
+        self.uids.get(name).cloned()
+    }
+
+    pub fn iter(&self) -> Iter {
+        Iter(self.uids.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.uids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uids.is_empty()
+    }
+}
+
+pub struct Iter<'a>(hash_map::Iter<'a, String, Uid>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, Uid);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(name, uid)| (name.as_str(), *uid))
+    }
+}
+
+impl<'a> IntoIterator for &'a AssignedUids {
+    type Item = (&'a str, Uid);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl IntoIterator for AssignedUids {
+    type Item = (String, Uid);
+    type IntoIter = hash_map::IntoIter<String, Uid>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.uids.into_iter()
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use futures::Future;
+
+use crate::client::Dgraph;
+use crate::errors::DgraphError;
+use crate::protos::api_grpc;
+use crate::protos::api;
+use crate::query::Vars;
+use crate::txn::{check_commit, merge_context};
+
+/// Non-blocking counterpart of `Txn`. All methods return std futures built on
+/// top of grpcio's async stubs, so they can be awaited inside an async runtime.
+pub struct AsyncTxn {
+    pub(super) context: api::TxnContext,
+    pub(super) finished: bool,
+    pub(super) read_only: bool,
+    pub(super) mutated: bool,
+    pub(super) dgraph: Dgraph,
+    pub(super) client: api_grpc::DgraphClient,
+}
+
+// Transactions own their handles, so they can be moved to other threads.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<AsyncTxn>();
+};
+
+// No call options are held across await points, so the futures can be
+// spawned on multi-threaded runtimes.
+#[allow(dead_code)]
+fn assert_futures_are_send(mut txn: AsyncTxn, dgraph: Dgraph, mu: api::Mutation, op: api::Operation) {
+    fn assert_send<T: Send>(_: &T) {}
+    assert_send(&txn.query(String::new()));
+    assert_send(&txn.mutate(mu));
+    assert_send(&txn.discard());
+    assert_send(&txn.commit());
+    assert_send(&dgraph.alter_async(&op));
+    assert_send(&dgraph.login_async(String::new(), String::new()));
+}
+
+/// Discard the transaction once it goes out of scope.
+/// The abort request is sent in the background, as drop cannot wait for it.
+impl Drop for AsyncTxn {
+    fn drop(&mut self) {
+        if self.finished || !self.mutated {
+            return;
+        }
+        self.finished = true;
+        self.context.aborted = true;
+
+        let call = self
+            .dgraph
+            .call_option()
+            .ok()
+            .and_then(|opt| self.client.commit_or_abort_async_opt(&self.context, opt).ok());
+
+        if let Some(call) = call {
+            self.client.spawn(call.map(|_| ()).map_err(|_| ()));
+        }
+    }
+}
+
+impl AsyncTxn {
+    pub async fn query(&mut self, query: impl Into<String>) -> Result<api::Response, DgraphError> {
+        self.query_with_vars(query, HashMap::new()).await
+    }
+
+    pub async fn query_with_vars(&mut self, query: impl Into<String>, vars: HashMap<String, String>) -> Result<api::Response, DgraphError> {
+        if self.finished {
+            return Err(DgraphError::TxnFinished);
+        }
+
+        let request = api::Request {
+            query: query.into(),
+            vars,
+            ..Default::default()
+        };
+        let res = self
+            .dgraph
+            .with_jwt_refresh_async(|opt| self.client.query_async_opt(&request, opt))
+            .await?;
+
+        let txn = match res.txn.as_ref() {
+            Some(txn) => txn,
+            None => return Err(DgraphError::MissingTxnContext)
+        };
+
+        merge_context(&mut self.context, txn)?;
+
+        Ok(res)
+    }
+
+    /// Runs the query with typed variables, failing before reaching the server
+    /// if the query uses a variable that is not declared or has no value.
+    pub async fn query_with_typed_vars(&mut self, query: impl Into<String>, vars: &Vars) -> Result<api::Response, DgraphError> {
+        let query = query.into();
+        vars.check(&query)?;
+        self.query_with_vars(query, vars.to_map()).await
+    }
+
+    pub async fn mutate(&mut self, mut mu: api::Mutation) -> Result<api::Assigned, DgraphError> {
+        match (self.finished, self.read_only) {
+            (true, _) => return Err(DgraphError::TxnFinished),
+            (_, true) => return Err(DgraphError::TxnReadOnly),
+            _ => ()
+        }
+
+        self.mutated = true;
+        mu.start_ts = self.context.start_ts;
+        let commit_now = mu.commit_now;
+        let mu_res = self
+            .dgraph
+            .with_jwt_refresh_async(|opt| self.client.mutate_async_opt(&mu, opt))
+            .await;
+
+        let mu_res = match mu_res {
+            Ok(mu_res) => mu_res,
+            Err(e) => {
+                let _ = self.discard().await;
+                return Err(e);
+            }
+        };
+
+        if commit_now {
+            self.finished = true;
+        }
+
+        let context = match mu_res.context.as_ref() {
+            Some(context) => context,
+            None => return Err(DgraphError::MissingTxnContext)
+        };
+
+        merge_context(&mut self.context, context)?;
+
+        Ok(mu_res)
+    }
+
+    pub async fn commit(mut self) -> Result<(), DgraphError> {
+        match (self.finished, self.read_only) {
+            (true, _) => return Err(DgraphError::TxnFinished),
+            (_, true) => return Err(DgraphError::TxnReadOnly),
+            _ => ()
+        }
+
+        self.commit_or_abort().await
+    }
+
+    pub async fn discard(&mut self) -> Result<(), DgraphError> {
+        self.context.aborted = true;
+        self.commit_or_abort().await
+    }
+
+    async fn commit_or_abort(&mut self) -> Result<(), DgraphError> {
+        if self.finished {
+            return Ok(())
+        }
+        self.finished = true;
+
+        if !self.mutated {
+            return Ok(())
+        }
+
+        let res = self
+            .dgraph
+            .with_jwt_refresh_async(|opt| self.client.commit_or_abort_async_opt(&self.context, opt))
+            .await?;
+
+        check_commit(&res, &self.context)
+    }
+}
@@ -1,15 +1,25 @@
-use std::sync::Mutex;
-use failure::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use futures03::compat::Future01CompatExt;
+use futures03::TryFutureExt;
+use grpcio::{CallOption, ClientUnaryReceiver, MetadataBuilder};
 use rand::prelude::*;
 
+use crate::async_txn::AsyncTxn;
+use crate::errors::DgraphError;
 use crate::protos::api_grpc;
 use crate::protos::api;
+use crate::retry::RetryPolicy;
 use crate::txn::Txn;
 
 // Dgraph is a transaction aware client to a set of dgraph server instances.
+// Cloning it is cheap, the clones share the connections and the login state,
+// which is what lets every transaction own a handle.
+#[derive(Clone)]
 pub struct Dgraph {
-    jwt: Mutex<api::Jwt>,
-    dc: Vec<api_grpc::DgraphClient>
+    jwt: Arc<Mutex<api::Jwt>>,
+    dc: Arc<Vec<api_grpc::DgraphClient>>
 }
 
 impl Dgraph {
@@ -22,14 +32,17 @@ impl Dgraph {
     /// A single client is thread safe for sharing with multiple go routines.
     pub fn new(clients: Vec<api_grpc::DgraphClient>) -> Dgraph {
         Dgraph {
-            jwt: Mutex::new(api::Jwt::new()),
-            dc: clients,
+            jwt: Arc::new(Mutex::new(api::Jwt::new())),
+            dc: Arc::new(clients),
         }
     }
 
-    pub fn login(&self, userid: String, password: String) -> Result<api::Response, Error> {
-        let _guard = self.jwt.lock().expect("Unable to block or acquire lock to jwt mutex");
-        let dc = self.any_client().expect("Cannot login. No client present");
+    /// Logs in the client using the provided credentials. The returned access
+    /// and refresh JWTs are stored in the client and sent along with all
+    /// subsequent requests.
+    pub fn login(&self, userid: String, password: String) -> Result<(), DgraphError> {
+        let mut jwt = self.jwt.lock().expect("Unable to block or acquire lock to jwt mutex");
+        let dc = self.any_client().ok_or(DgraphError::NoClients)?;
 
         let login_request = api::LoginRequest {
             userid,
@@ -38,39 +51,380 @@ impl Dgraph {
         };
 
         let res = dc.login(&login_request)?;
+        *jwt = protobuf::parse_from_bytes(&res.json)?;
 
-        unimplemented!()
+        Ok(())
     }
 
-    pub fn retry_login(&self, userid: String, password: String) -> Result<api::Response, Error> {
-        unimplemented!()
+    /// Obtains a new pair of access and refresh JWTs using the refresh JWT
+    /// stored by a previous call to `login`.
+    pub fn retry_login(&self) -> Result<(), DgraphError> {
+        let mut jwt = self.jwt.lock().expect("Unable to block or acquire lock to jwt mutex");
+        if jwt.refresh_jwt.is_empty() {
+            return Err(DgraphError::EmptyRefreshJwt);
+        }
+
+        let dc = self.any_client().ok_or(DgraphError::NoClients)?;
+
+        let login_request = api::LoginRequest {
+            refresh_token: jwt.refresh_jwt.clone(),
+            ..Default::default()
+        };
+
+        let res = dc.login(&login_request)?;
+        *jwt = protobuf::parse_from_bytes(&res.json)?;
+
+        Ok(())
+    }
+
+    /// Async counterpart of `login`.
+    pub async fn login_async(&self, userid: String, password: String) -> Result<(), DgraphError> {
+        let dc = self.any_client().ok_or(DgraphError::NoClients)?;
+
+        let login_request = api::LoginRequest {
+            userid,
+            password,
+            ..Default::default()
+        };
+
+        let res = dc.login_async(&login_request)?.compat().await?;
+        let jwt = protobuf::parse_from_bytes(&res.json)?;
+        *self.jwt.lock().expect("Unable to block or acquire lock to jwt mutex") = jwt;
+
+        Ok(())
+    }
+
+    /// Async counterpart of `retry_login`.
+    pub async fn retry_login_async(&self) -> Result<(), DgraphError> {
+        let refresh_jwt = self.jwt.lock().expect("Unable to block or acquire lock to jwt mutex").refresh_jwt.clone();
+        if refresh_jwt.is_empty() {
+            return Err(DgraphError::EmptyRefreshJwt);
+        }
+
+        let dc = self.any_client().ok_or(DgraphError::NoClients)?;
+
+        let login_request = api::LoginRequest {
+            refresh_token: refresh_jwt,
+            ..Default::default()
+        };
+
+        let res = dc.login_async(&login_request)?.compat().await?;
+        let jwt = protobuf::parse_from_bytes(&res.json)?;
+        *self.jwt.lock().expect("Unable to block or acquire lock to jwt mutex") = jwt;
+
+        Ok(())
+    }
+
+    pub fn alter(&self, op: &api::Operation) -> Result<api::Payload, DgraphError> {
+        let dc = self.any_client().ok_or(DgraphError::NoClients)?;
+        let res = self.with_jwt_refresh(|opt| dc.alter_opt(op, opt))?;
+        Ok(res)
+    }
+
+    /// Returns call options carrying the access JWT, if the client is logged in.
+    pub(crate) fn call_option(&self) -> Result<CallOption, DgraphError> {
+        let jwt = self.jwt.lock().expect("Unable to block or acquire lock to jwt mutex");
+        if jwt.access_jwt.is_empty() {
+            return Ok(CallOption::default());
+        }
+
+        let mut metadata = MetadataBuilder::new();
+        metadata.add_str("accessJwt", &jwt.access_jwt)?;
+
+        Ok(CallOption::default().headers(metadata.build()))
+    }
+
+    /// Runs the gRPC call with the current call options. If it fails because the
+    /// access JWT has expired, the JWTs are refreshed and the call is replayed once.
+    pub(crate) fn with_jwt_refresh<T, F>(&self, call: F) -> Result<T, DgraphError>
+    where
+        F: Fn(CallOption) -> grpcio::Result<T>,
+    {
+        refresh_once(|| Ok(call(self.call_option()?)?), || self.retry_login())
     }
 
-    pub fn alter(&self, op: &api::Operation) -> Result<api::Payload, Error> {
-        let dc = self.any_client().expect("Cannot alter. No client present");
-        let res = dc.alter(op)?;
+    /// Async counterpart of `alter`.
+    pub async fn alter_async(&self, op: &api::Operation) -> Result<api::Payload, DgraphError> {
+        let dc = self.any_client().ok_or(DgraphError::NoClients)?;
+        let res = self.with_jwt_refresh_async(|opt| dc.alter_async_opt(op, opt)).await?;
         Ok(res)
     }
 
+    /// Async counterpart of `with_jwt_refresh`.
+    pub(crate) async fn with_jwt_refresh_async<T, F>(&self, call: F) -> Result<T, DgraphError>
+    where
+        F: Fn(CallOption) -> grpcio::Result<ClientUnaryReceiver<T>>,
+    {
+        // The call options are not Send, so they must not be kept across an await point.
+        refresh_once_async(
+            || Ok(call(self.call_option()?)?.compat().err_into()),
+            self.retry_login_async(),
+        )
+        .await
+    }
+
     pub fn any_client(&self) -> Option<&api_grpc::DgraphClient> {
         let mut rng = thread_rng();
 
         self.dc.choose(&mut rng)
     }
 
-    pub fn new_txn(&self) -> Txn {
-        Txn {
+    /// Creates a new transaction on one of the clients, or fails with
+    /// `DgraphError::NoClients` if there is none.
+    pub fn new_txn(&self) -> Result<Txn, DgraphError> {
+        Ok(Txn {
             context: Default::default(),
             finished: false,
             mutated: false,
             read_only: false,
-            client: self.any_client().expect("Cannot create transactions. No client present!")
-        }
+            dgraph: self.clone(),
+            client: self.any_client().ok_or(DgraphError::NoClients)?.clone(),
+        })
+    }
+
+    pub fn new_readonly_txn(&self) -> Result<Txn, DgraphError> {
+        let mut txn = self.new_txn()?;
+        txn.read_only = true;
+        Ok(txn)
+    }
+
+    pub fn new_async_txn(&self) -> Result<AsyncTxn, DgraphError> {
+        Ok(AsyncTxn {
+            context: Default::default(),
+            finished: false,
+            mutated: false,
+            read_only: false,
+            dgraph: self.clone(),
+            client: self.any_client().ok_or(DgraphError::NoClients)?.clone(),
+        })
     }
 
-    pub fn new_readonly_txn(&self) -> Txn {
-        let mut txn = self.new_txn();
+    pub fn new_async_readonly_txn(&self) -> Result<AsyncTxn, DgraphError> {
+        let mut txn = self.new_async_txn()?;
         txn.read_only = true;
-        txn
+        Ok(txn)
+    }
+
+    /// Runs the closure in a new transaction and commits it. If the transaction
+    /// is aborted because of a conflict, the closure is run again in a fresh
+    /// transaction according to the default `RetryPolicy`.
+    pub fn transaction<T, F>(&self, f: F) -> Result<T, DgraphError>
+    where
+        F: FnMut(&mut Txn) -> Result<T, DgraphError>,
+    {
+        self.transaction_with_policy(&RetryPolicy::default(), f)
+    }
+
+    /// Same as `transaction`, but retries aborted transactions according to
+    /// the given policy. Any other error is returned right away.
+    pub fn transaction_with_policy<T, F>(&self, policy: &RetryPolicy, mut f: F) -> Result<T, DgraphError>
+    where
+        F: FnMut(&mut Txn) -> Result<T, DgraphError>,
+    {
+        let mut attempt = 1;
+        loop {
+            let mut txn = self.new_txn()?;
+            let res = f(&mut txn).and_then(|value| {
+                // Mutations with commit_now have already finished the transaction.
+                if !txn.finished {
+                    txn.commit()?;
+                }
+                Ok(value)
+            });
+
+            match res {
+                Err(err) => match policy.retry_after(attempt, &err) {
+                    Some(delay) => {
+                        thread::sleep(delay);
+                        attempt += 1;
+                    }
+                    None => return Err(err),
+                },
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Runs the call and, if it fails because the access JWT has expired, runs
+/// `refresh` and replays the call once. A second failure is returned as is.
+fn refresh_once<T>(
+    mut call: impl FnMut() -> Result<T, DgraphError>,
+    refresh: impl FnOnce() -> Result<(), DgraphError>,
+) -> Result<T, DgraphError> {
+    match call() {
+        Err(ref err) if is_jwt_expired(err) => {
+            refresh()?;
+            call()
+        }
+        res => res,
+    }
+}
+
+/// Async counterpart of `refresh_once`. The call returns the future of the
+/// request, so that the request is only sent when the call is made.
+async fn refresh_once_async<T, C, F, R>(mut call: C, refresh: R) -> Result<T, DgraphError>
+where
+    C: FnMut() -> Result<F, DgraphError>,
+    F: Future<Output = Result<T, DgraphError>>,
+    R: Future<Output = Result<(), DgraphError>>,
+{
+    match call()?.await {
+        Err(ref err) if is_jwt_expired(err) => {
+            refresh.await?;
+            call()?.await
+        }
+        res => res,
+    }
+}
+
+fn is_jwt_expired(err: &DgraphError) -> bool {
+    match err {
+        DgraphError::Unauthenticated(message) => message.contains("Token is expired"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use futures03::executor::block_on;
+    use futures03::future::ready;
+
+    use super::*;
+
+    fn unauthenticated(message: &str) -> DgraphError {
+        DgraphError::Unauthenticated(message.to_string())
+    }
+
+    #[test]
+    fn refreshes_expired_jwt_once() {
+        let (calls, refreshes) = (Cell::new(0), Cell::new(0));
+        let res = refresh_once(
+            || {
+                calls.set(calls.get() + 1);
+                if calls.get() == 1 {
+                    Err(unauthenticated("Token is expired"))
+                } else {
+                    Ok(calls.get())
+                }
+            },
+            || {
+                refreshes.set(refreshes.get() + 1);
+                Ok(())
+            },
+        );
+
+        assert_eq!(res.unwrap(), 2);
+        assert_eq!((calls.get(), refreshes.get()), (2, 1));
+    }
+
+    #[test]
+    fn does_not_refresh_on_other_auth_failures() {
+        let (calls, refreshes) = (Cell::new(0), Cell::new(0));
+        let res: Result<(), _> = refresh_once(
+            || {
+                calls.set(calls.get() + 1);
+                Err(unauthenticated("no accessJwt available"))
+            },
+            || {
+                refreshes.set(refreshes.get() + 1);
+                Ok(())
+            },
+        );
+
+        match res {
+            Err(DgraphError::Unauthenticated(message)) => assert_eq!(message, "no accessJwt available"),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!((calls.get(), refreshes.get()), (1, 0));
+    }
+
+    #[test]
+    fn returns_second_failure_unchanged() {
+        let calls = Cell::new(0);
+        let res: Result<(), _> = refresh_once(
+            || {
+                calls.set(calls.get() + 1);
+                Err(unauthenticated(&format!("Token is expired ({})", calls.get())))
+            },
+            || Ok(()),
+        );
+
+        match res {
+            Err(DgraphError::Unauthenticated(message)) => assert_eq!(message, "Token is expired (2)"),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn returns_refresh_failure() {
+        let calls = Cell::new(0);
+        let res: Result<(), _> = refresh_once(
+            || {
+                calls.set(calls.get() + 1);
+                Err(unauthenticated("Token is expired"))
+            },
+            || Err(DgraphError::EmptyRefreshJwt),
+        );
+
+        match res {
+            Err(DgraphError::EmptyRefreshJwt) => (),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn refreshes_expired_jwt_once_async() {
+        let (calls, refreshes) = (Cell::new(0), Cell::new(0));
+        let refresh = async {
+            refreshes.set(refreshes.get() + 1);
+            Ok(())
+        };
+        let res = block_on(refresh_once_async(
+            || {
+                calls.set(calls.get() + 1);
+                Ok(ready(if calls.get() == 1 {
+                    Err(unauthenticated("Token is expired"))
+                } else {
+                    Ok(calls.get())
+                }))
+            },
+            refresh,
+        ));
+
+        assert_eq!(res.unwrap(), 2);
+        assert_eq!((calls.get(), refreshes.get()), (2, 1));
+
+        calls.set(0);
+        let res: Result<(), _> = block_on(refresh_once_async(
+            || {
+                calls.set(calls.get() + 1);
+                Ok(ready(Err(unauthenticated("Token is expired"))))
+            },
+            async { Ok(()) },
+        ));
+        assert!(res.is_err());
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn does_not_refresh_on_other_auth_failures_async() {
+        let (calls, refreshes) = (Cell::new(0), Cell::new(0));
+        let res: Result<(), _> = block_on(refresh_once_async(
+            || {
+                calls.set(calls.get() + 1);
+                Ok(ready(Err(unauthenticated("no accessJwt available"))))
+            },
+            async {
+                refreshes.set(refreshes.get() + 1);
+                Ok(())
+            },
+        ));
+
+        assert!(res.is_err());
+        assert_eq!((calls.get(), refreshes.get()), (1, 0));
     }
 }
@@ -0,0 +1,236 @@
+use std::convert::TryInto;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::errors::DgraphError;
+use crate::facet::{facet_bytes, FromFacetValue, ToFacetValue};
+use crate::protos::api;
+use crate::protos::api::Value_oneof_val::*;
+use crate::value::{mismatch, FromDgraphValue, ToDgraphValue};
+
+/// Seconds between January 1 of year 1, where Go's time encoding starts, and
+/// the Unix epoch.
+const SECONDS_TO_UNIX_EPOCH: i64 = 62_135_596_800;
+
+/// Encodes the datetime the way the server stores it, which is the binary
+/// encoding of Go's `time.Time` with the UTC location.
+pub(crate) fn encode_datetime(datetime: &DateTime<Utc>) -> Vec<u8> {
+    let sec = datetime.timestamp() + SECONDS_TO_UNIX_EPOCH;
+    let nsec = datetime.timestamp_subsec_nanos() as i32;
+    // Go encodes the UTC location with an offset of -1 minutes.
+    let offset_min: i16 = -1;
+
+    let mut bytes = Vec::with_capacity(15);
+    bytes.push(1);
+    bytes.extend_from_slice(&sec.to_be_bytes());
+    bytes.extend_from_slice(&nsec.to_be_bytes());
+    bytes.extend_from_slice(&offset_min.to_be_bytes());
+    bytes
+}
+
+/// Decodes a datetime encoded by the server. The zone offset is dropped, as the
+/// encoded seconds are absolute.
+pub(crate) fn decode_datetime(bytes: &[u8]) -> Result<DateTime<Utc>, DgraphError> {
+    let invalid = || DgraphError::InvalidDateTime(format!("{:?}", bytes));
+
+    // Version 2 of the encoding adds the seconds of the zone offset at the end.
+    match (bytes.first(), bytes.len()) {
+        (Some(1), 15) | (Some(2), 16) => (),
+        _ => return Err(invalid()),
+    }
+
+    let sec = i64::from_be_bytes(bytes[1..9].try_into().map_err(|_| invalid())?);
+    let nsec = i32::from_be_bytes(bytes[9..13].try_into().map_err(|_| invalid())?);
+
+    Utc.timestamp_opt(sec - SECONDS_TO_UNIX_EPOCH, nsec as u32)
+        .single()
+        .ok_or_else(invalid)
+}
+
+/// Parses the datetime formats accepted by the server: RFC 3339, and the same
+/// without zone or time, which are taken as UTC.
+pub(crate) fn parse_datetime(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Some(Utc.from_utc_datetime(&datetime));
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| Utc.from_utc_datetime(&datetime))
+}
+
+pub(crate) fn format_datetime(datetime: &DateTime<Utc>) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string()
+}
+
+impl ToDgraphValue for DateTime<Utc> {
+    fn to_dgraph_value(&self) -> api::Value {
+        let mut value = api::Value::new();
+        value.set_datetime_val(encode_datetime(self));
+        value
+    }
+}
+
+/// Datetimes are read from both `datetime_val` and `date_val`.
+impl FromDgraphValue for DateTime<Utc> {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(datetime_val(ref bytes)) | Some(date_val(ref bytes)) => decode_datetime(bytes),
+            _ => Err(mismatch("datetime_val", value)),
+        }
+    }
+}
+
+/// Dates are stored as datetimes at midnight UTC.
+impl ToDgraphValue for NaiveDate {
+    fn to_dgraph_value(&self) -> api::Value {
+        let midnight = self.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+        let mut value = api::Value::new();
+        value.set_date_val(encode_datetime(&Utc.from_utc_datetime(&midnight)));
+        value
+    }
+}
+
+/// Dates are read from both `date_val` and `datetime_val`, dropping the time.
+impl FromDgraphValue for NaiveDate {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(date_val(ref bytes)) | Some(datetime_val(ref bytes)) => {
+                Ok(decode_datetime(bytes)?.naive_utc().date())
+            }
+            _ => Err(mismatch("date_val", value)),
+        }
+    }
+}
+
+impl From<DateTime<Utc>> for api::Value {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        datetime.to_dgraph_value()
+    }
+}
+
+impl From<NaiveDate> for api::Value {
+    fn from(date: NaiveDate) -> Self {
+        date.to_dgraph_value()
+    }
+}
+
+impl ToFacetValue for DateTime<Utc> {
+    fn to_facet_value(&self) -> (api::Facet_ValType, Vec<u8>) {
+        (api::Facet_ValType::DATETIME, encode_datetime(self))
+    }
+}
+
+impl FromFacetValue for DateTime<Utc> {
+    fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError> {
+        decode_datetime(facet_bytes(facet, api::Facet_ValType::DATETIME)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::FixedOffset;
+
+    use super::*;
+
+    /// Go's `time.Date(2006, 1, 2, 15, 4, 5, 0, time.UTC).MarshalBinary()`.
+    const REFERENCE: [u8; 15] = [1, 0, 0, 0, 14, 187, 75, 55, 229, 0, 0, 0, 0, 255, 255];
+
+    fn naive(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).and_then(|date| date.and_hms_opt(hour, min, sec)).unwrap()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&naive(year, month, day, hour, min, sec))
+    }
+
+    fn reference() -> DateTime<Utc> {
+        utc(2006, 1, 2, 15, 4, 5)
+    }
+
+    #[test]
+    fn encodes_like_go() {
+        assert_eq!(encode_datetime(&reference()), REFERENCE);
+        assert_eq!(decode_datetime(&REFERENCE).unwrap(), reference());
+    }
+
+    #[test]
+    fn encodes_nanoseconds() {
+        let datetime = reference() + chrono::Duration::nanoseconds(123_456_789);
+        let bytes = [1, 0, 0, 0, 14, 187, 75, 55, 229, 7, 91, 205, 21, 255, 255];
+        assert_eq!(encode_datetime(&datetime), bytes);
+        assert_eq!(decode_datetime(&bytes).unwrap(), datetime);
+    }
+
+    #[test]
+    fn encodes_other_offsets_as_utc() {
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        let datetime = offset.from_local_datetime(&naive(2006, 1, 2, 17, 4, 5)).unwrap();
+        assert_eq!(encode_datetime(&datetime.with_timezone(&Utc)), REFERENCE);
+    }
+
+    #[test]
+    fn decodes_other_offsets() {
+        // Same instant, encoded by Go with a zone of +02:00, in both versions.
+        let mut v1 = REFERENCE.to_vec();
+        v1[13..].copy_from_slice(&[0, 120]);
+        assert_eq!(decode_datetime(&v1).unwrap(), reference());
+
+        let mut v2 = v1.clone();
+        v2[0] = 2;
+        v2.push(0);
+        assert_eq!(decode_datetime(&v2).unwrap(), reference());
+    }
+
+    #[test]
+    fn encodes_dates_before_the_epoch() {
+        let datetime = utc(1969, 12, 31, 23, 59, 59);
+        let bytes = [1, 0, 0, 0, 14, 119, 145, 246, 255, 0, 0, 0, 0, 255, 255];
+        assert_eq!(encode_datetime(&datetime), bytes);
+        assert_eq!(decode_datetime(&bytes).unwrap(), datetime);
+
+        let year_one = utc(1, 1, 1, 0, 0, 0);
+        assert_eq!(encode_datetime(&year_one), [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn encodes_dates_at_midnight() {
+        let date = NaiveDate::from_ymd_opt(2006, 1, 2).unwrap();
+        let value = date.to_dgraph_value();
+        assert_eq!(value.get_date_val(), [1, 0, 0, 0, 14, 187, 74, 100, 0, 0, 0, 0, 0, 255, 255]);
+        assert_eq!(NaiveDate::from_dgraph_value(&value).unwrap(), date);
+        assert_eq!(NaiveDate::from_dgraph_value(&reference().to_dgraph_value()).unwrap(), date);
+    }
+
+    #[test]
+    fn rejects_malformed_encodings() {
+        let mut wrong_version = REFERENCE;
+        wrong_version[0] = 3;
+        let mut bad_nanos = REFERENCE;
+        bad_nanos[9..13].copy_from_slice(&[255, 255, 255, 255]);
+
+        let malformed: Vec<&[u8]> = vec![&[], &[1], &REFERENCE[..14], &wrong_version, &bad_nanos];
+        for bytes in malformed {
+            match decode_datetime(bytes) {
+                Err(DgraphError::InvalidDateTime(_)) => (),
+                res => panic!("unexpected {:?} for {:?}", res, bytes),
+            }
+        }
+
+        let mut v2_too_short = REFERENCE;
+        v2_too_short[0] = 2;
+        assert!(decode_datetime(&v2_too_short).is_err());
+    }
+
+    #[test]
+    fn parses_and_formats_text() {
+        assert_eq!(parse_datetime("2006-01-02T15:04:05Z"), Some(reference()));
+        assert_eq!(parse_datetime("2006-01-02T17:04:05+02:00"), Some(reference()));
+        assert_eq!(parse_datetime("2006-01-02T15:04:05"), Some(reference()));
+        assert_eq!(parse_datetime("2006-01-02").map(|dt| format_datetime(&dt)), Some("2006-01-02T00:00:00Z".to_string()));
+        assert_eq!(parse_datetime("02/01/2006"), None);
+        assert_eq!(format_datetime(&(reference() + chrono::Duration::milliseconds(120))), "2006-01-02T15:04:05.120Z");
+    }
+}
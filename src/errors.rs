@@ -0,0 +1,149 @@
+use failure::Fail;
+use grpcio::RpcStatusCode;
+
+use crate::schema::{SchemaDiff, SchemaError};
+
+/// Errors returned by `Dgraph` and `Txn` methods.
+#[derive(Debug, Fail)]
+pub enum DgraphError {
+    #[fail(display = "Transaction has already been committed or discarded")]
+    TxnFinished,
+    #[fail(display = "Transaction is read-only")]
+    TxnReadOnly,
+    #[fail(display = "Transaction has been aborted. Please retry")]
+    TxnAborted,
+    #[fail(display = "Missing Txn context on response")]
+    MissingTxnContext,
+    #[fail(display = "Start timestamp mismatch: expected {}, got {}", expected, got)]
+    StartTsMismatch { expected: u64, got: u64 },
+    #[fail(display = "No Dgraph client present")]
+    NoClients,
+    #[fail(display = "Refresh jwt should not be empty")]
+    EmptyRefreshJwt,
+    #[fail(display = "Invalid jwt in login response: {}", _0)]
+    InvalidJwt(#[cause] protobuf::ProtobufError),
+    #[fail(display = "Invalid uid: {}", _0)]
+    InvalidUid(String),
+    #[fail(display = "Mutation was committed, but the server returned an invalid uid: {}", _0)]
+    CommittedWithInvalidUid(String),
+    #[fail(display = "Expected {} value, found {}", expected, found)]
+    ValueTypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[fail(display = "Integer {} does not fit in {}", value, target)]
+    IntOutOfRange { value: i128, target: &'static str },
+    #[fail(display = "Float {} cannot be represented exactly as {}", value, target)]
+    FloatPrecisionLoss { value: f64, target: &'static str },
+    #[fail(display = "Invalid datetime value: {}", _0)]
+    InvalidDateTime(String),
+    #[fail(display = "Invalid geo value: {}", _0)]
+    InvalidGeo(String),
+    #[fail(display = "Invalid facet: {}", _0)]
+    InvalidFacet(String),
+    #[fail(display = "Query variable {} is used but not declared", _0)]
+    UndeclaredVar(String),
+    #[fail(display = "Query variable {} has no value and no default", _0)]
+    UnsetVar(String),
+    #[fail(display = "Invalid schema: {}", _0)]
+    InvalidSchema(#[cause] SchemaError),
+    #[fail(display = "Schema returned in the JSON payload, which is only read with the serde feature")]
+    JsonSchemaUnsupported,
+    #[fail(display = "Schema of the cluster differs from the expected one:\n{}", _0)]
+    SchemaDrift(SchemaDiff),
+    #[fail(display = "Unauthenticated: {}", _0)]
+    Unauthenticated(String),
+    #[fail(display = "Permission denied: {}", _0)]
+    PermissionDenied(String),
+    #[fail(display = "Grpc error: {}", _0)]
+    GrpcError(#[cause] grpcio::Error),
+    #[cfg(feature = "serde")]
+    #[fail(display = "Unable to encode mutation as json: {}", _0)]
+    JsonEncode(#[cause] serde_json::Error),
+    #[cfg(feature = "serde")]
+    #[fail(display = "Unable to decode query response at `{}`: {}", path, err)]
+    JsonDecode {
+        path: String,
+        #[cause]
+        err: serde_json::Error,
+    },
+}
+
+/// The server reports a transaction that lost a conflict with the `Aborted`
+/// status code, which is kept apart from other gRPC failures so that callers
+/// know the transaction can be retried. Auth failures get their own variants
+/// as well, carrying the message of the server.
+impl From<grpcio::Error> for DgraphError {
+    fn from(err: grpcio::Error) -> Self {
+        match err {
+            grpcio::Error::RpcFailure(status) => match status.status {
+                RpcStatusCode::Aborted => DgraphError::TxnAborted,
+                RpcStatusCode::Unauthenticated => DgraphError::Unauthenticated(status.details.unwrap_or_default()),
+                RpcStatusCode::PermissionDenied => DgraphError::PermissionDenied(status.details.unwrap_or_default()),
+                _ => DgraphError::GrpcError(grpcio::Error::RpcFailure(status)),
+            },
+            err => DgraphError::GrpcError(err),
+        }
+    }
+}
+
+impl From<protobuf::ProtobufError> for DgraphError {
+    fn from(err: protobuf::ProtobufError) -> Self {
+        DgraphError::InvalidJwt(err)
+    }
+}
+
+impl From<SchemaError> for DgraphError {
+    fn from(err: SchemaError) -> Self {
+        DgraphError::InvalidSchema(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grpcio::RpcStatus;
+
+    use super::*;
+
+    fn rpc_failure(status: RpcStatusCode, details: &str) -> DgraphError {
+        grpcio::Error::RpcFailure(RpcStatus::new(status, Some(details.to_string()))).into()
+    }
+
+    #[test]
+    fn maps_aborted_status_to_txn_aborted() {
+        match rpc_failure(RpcStatusCode::Aborted, "Transaction has been aborted. Please retry") {
+            DgraphError::TxnAborted => (),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn keeps_other_failures_as_grpc_errors() {
+        match rpc_failure(RpcStatusCode::Unavailable, "connection refused") {
+            DgraphError::GrpcError(grpcio::Error::RpcFailure(status)) => {
+                assert_eq!(status.status, RpcStatusCode::Unavailable);
+                assert_eq!(status.details, Some("connection refused".to_string()));
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+        match DgraphError::from(grpcio::Error::RemoteStopped) {
+            DgraphError::GrpcError(grpcio::Error::RemoteStopped) => (),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn maps_auth_failures() {
+        match rpc_failure(RpcStatusCode::Unauthenticated, "Token is expired") {
+            DgraphError::Unauthenticated(message) => assert_eq!(message, "Token is expired"),
+            err => panic!("unexpected error {:?}", err),
+        }
+        match rpc_failure(RpcStatusCode::PermissionDenied, "unauthorized to mutate") {
+            DgraphError::PermissionDenied(message) => assert_eq!(message, "unauthorized to mutate"),
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let err: DgraphError = grpcio::Error::RpcFailure(RpcStatus::new(RpcStatusCode::Unauthenticated, None)).into();
+        assert_eq!(err.to_string(), "Unauthenticated: ");
+    }
+}
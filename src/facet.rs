@@ -0,0 +1,404 @@
+use std::convert::TryInto;
+
+use crate::errors::DgraphError;
+use crate::protos::api;
+use crate::protos::api::Facet_ValType;
+
+/// Conversion of Rust values into the type and bytes of an `api::Facet`.
+pub trait ToFacetValue {
+    fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>);
+}
+
+/// Conversion of the bytes of an `api::Facet` back into a Rust value. A facet
+/// of another type than the one expected is reported as an error.
+pub trait FromFacetValue: Sized {
+    fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError>;
+}
+
+impl api::Facet {
+    /// Builds the facet `key`, encoding the value as the server does for its type.
+    pub fn with_value(key: impl Into<String>, value: impl ToFacetValue) -> api::Facet {
+        let (val_type, bytes) = value.to_facet_value();
+        let mut facet = api::Facet::new();
+        facet.key = key.into();
+        facet.value = bytes;
+        facet.val_type = val_type;
+        facet
+    }
+
+    /// Decodes the value of the facet.
+    pub fn value_as<T: FromFacetValue>(&self) -> Result<T, DgraphError> {
+        T::from_facet(self)
+    }
+}
+
+fn type_name(val_type: Facet_ValType) -> &'static str {
+    match val_type {
+        Facet_ValType::STRING => "STRING",
+        Facet_ValType::INT => "INT",
+        Facet_ValType::FLOAT => "FLOAT",
+        Facet_ValType::BOOL => "BOOL",
+        Facet_ValType::DATETIME => "DATETIME",
+    }
+}
+
+/// Returns the bytes of the facet, checking that it has the expected type.
+pub(crate) fn facet_bytes(facet: &api::Facet, expected: Facet_ValType) -> Result<&[u8], DgraphError> {
+    if facet.val_type != expected {
+        return Err(DgraphError::ValueTypeMismatch {
+            expected: type_name(expected),
+            found: type_name(facet.val_type),
+        });
+    }
+    Ok(&facet.value)
+}
+
+pub(crate) fn malformed(facet: &api::Facet) -> DgraphError {
+    DgraphError::InvalidFacet(format!("malformed {} facet {:?}", type_name(facet.val_type), facet.key))
+}
+
+impl ToFacetValue for i64 {
+    fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>) {
+        (Facet_ValType::INT, self.to_le_bytes().to_vec())
+    }
+}
+
+impl FromFacetValue for i64 {
+    fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError> {
+        let bytes = facet_bytes(facet, Facet_ValType::INT)?;
+        let bytes = bytes.try_into().map_err(|_| malformed(facet))?;
+        Ok(i64::from_le_bytes(bytes))
+    }
+}
+
+macro_rules! int_facet {
+    ($($ty:ty),*) => {
+        $(
+            impl ToFacetValue for $ty {
+                fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>) {
+                    i64::from(*self).to_facet_value()
+                }
+            }
+
+            impl FromFacetValue for $ty {
+                fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError> {
+                    let int = i64::from_facet(facet)?;
+                    if int < <$ty>::min_value() as i64 || int > <$ty>::max_value() as i64 {
+                        return Err(DgraphError::IntOutOfRange {
+                            value: i128::from(int),
+                            target: stringify!($ty),
+                        });
+                    }
+                    Ok(int as $ty)
+                }
+            }
+        )*
+    };
+}
+
+int_facet!(i8, i16, i32, u8, u16, u32);
+
+impl ToFacetValue for f64 {
+    fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>) {
+        (Facet_ValType::FLOAT, self.to_bits().to_le_bytes().to_vec())
+    }
+}
+
+impl FromFacetValue for f64 {
+    fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError> {
+        let bytes = facet_bytes(facet, Facet_ValType::FLOAT)?;
+        let bytes = bytes.try_into().map_err(|_| malformed(facet))?;
+        Ok(f64::from_bits(u64::from_le_bytes(bytes)))
+    }
+}
+
+impl ToFacetValue for f32 {
+    fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>) {
+        f64::from(*self).to_facet_value()
+    }
+}
+
+/// Only floats holding an `f32` exactly are read, like `f32` values.
+impl FromFacetValue for f32 {
+    fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError> {
+        let float = f64::from_facet(facet)?;
+        if float.is_nan() || f64::from(float as f32) == float {
+            Ok(float as f32)
+        } else {
+            Err(DgraphError::FloatPrecisionLoss {
+                value: float,
+                target: "f32",
+            })
+        }
+    }
+}
+
+impl ToFacetValue for bool {
+    fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>) {
+        (Facet_ValType::BOOL, vec![*self as u8])
+    }
+}
+
+impl FromFacetValue for bool {
+    fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError> {
+        match facet_bytes(facet, Facet_ValType::BOOL)? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(malformed(facet)),
+        }
+    }
+}
+
+impl ToFacetValue for &str {
+    fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>) {
+        (Facet_ValType::STRING, self.as_bytes().to_vec())
+    }
+}
+
+impl ToFacetValue for String {
+    fn to_facet_value(&self) -> (Facet_ValType, Vec<u8>) {
+        self.as_str().to_facet_value()
+    }
+}
+
+impl FromFacetValue for String {
+    fn from_facet(facet: &api::Facet) -> Result<Self, DgraphError> {
+        let bytes = facet_bytes(facet, Facet_ValType::STRING)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed(facet))
+    }
+}
+
+/// Facets of a JSON query response or mutation, which Dgraph writes next to
+/// the predicates as `predicate|facet` keys. Meant to be flattened into the
+/// struct of the node:
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Person {
+///     name: String,
+///     #[serde(flatten)]
+///     facets: dgraph::Facets,
+/// }
+///
+/// let alice: Person = serde_json::from_str(r#"{"name": "Alice", "name|origin": "french"}"#).unwrap();
+/// let origin: Option<String> = alice.facets.get("name", "origin").unwrap();
+/// assert_eq!(origin, Some("french".to_string()));
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Facets {
+    facets: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::fmt;
+    use serde::de::{Deserialize, DeserializeOwned, Deserializer, IgnoredAny, MapAccess, Visitor};
+    use serde::ser::{Serialize, SerializeMap, Serializer};
+    use serde_json::Value;
+
+    use super::Facets;
+    use crate::errors::DgraphError;
+
+    fn facet_key(predicate: &str, facet: &str) -> String {
+        format!("{}|{}", predicate, facet)
+    }
+
+    impl Facets {
+        pub fn new() -> Self {
+            Facets::default()
+        }
+
+        /// Decodes the facet of the predicate, if present.
+        pub fn get<T: DeserializeOwned>(&self, predicate: &str, facet: &str) -> Result<Option<T>, DgraphError> {
+            match self.facets.get(&facet_key(predicate, facet)) {
+                Some(value) => T::deserialize(value).map(Some).map_err(|err| DgraphError::JsonDecode {
+                    path: format!("$.{}", facet_key(predicate, facet)),
+                    err,
+                }),
+                None => Ok(None),
+            }
+        }
+
+        pub fn insert<T: Serialize>(&mut self, predicate: &str, facet: &str, value: T) -> Result<(), DgraphError> {
+            let value = serde_json::to_value(value).map_err(DgraphError::JsonEncode)?;
+            self.facets.insert(facet_key(predicate, facet), value);
+            Ok(())
+        }
+
+        /// Iterates over the facets as `(predicate, facet, value)`.
+        pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &Value)> {
+            self.facets.iter().map(|(key, value)| {
+                let mut parts = key.splitn(2, '|');
+                let predicate = parts.next().unwrap_or_default();
+                (predicate, parts.next().unwrap_or_default(), value)
+            })
+        }
+
+        pub fn len(&self) -> usize {
+            self.facets.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.facets.is_empty()
+        }
+    }
+
+    impl Serialize for Facets {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(self.facets.len()))?;
+            for (key, value) in self.facets.iter() {
+                map.serialize_entry(key, value)?;
+            }
+            map.end()
+        }
+    }
+
+    struct FacetsVisitor;
+
+    impl<'de> Visitor<'de> for FacetsVisitor {
+        type Value = Facets;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map with `predicate|facet` keys")
+        }
+
+        /// Keys without a `|` are the predicates themselves and are skipped.
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Facets, A::Error> {
+            let mut facets = Facets::new();
+            while let Some(key) = access.next_key::<String>()? {
+                if key.contains('|') {
+                    facets.facets.insert(key, access.next_value()?);
+                } else {
+                    access.next_value::<IgnoredAny>()?;
+                }
+            }
+            Ok(facets)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Facets {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Facets, D::Error> {
+            deserializer.deserialize_map(FacetsVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: ToFacetValue + FromFacetValue>(value: T) -> T {
+        api::Facet::with_value("key", value).value_as().unwrap()
+    }
+
+    #[test]
+    fn encodes_ints_as_little_endian() {
+        let facet = api::Facet::with_value("since", 2006);
+        assert_eq!(facet.val_type, Facet_ValType::INT);
+        assert_eq!(facet.value, vec![214, 7, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(round_trip(-3i64), -3);
+        assert_eq!(round_trip(255u8), 255);
+    }
+
+    #[test]
+    fn encodes_floats_as_little_endian() {
+        let facet = api::Facet::with_value("weight", 1.5);
+        assert_eq!(facet.val_type, Facet_ValType::FLOAT);
+        assert_eq!(facet.value, vec![0, 0, 0, 0, 0, 0, 248, 63]);
+        assert_eq!(round_trip(-0.1f64), -0.1);
+        assert_eq!(round_trip(0.1f32), 0.1);
+    }
+
+    #[test]
+    fn encodes_bools_and_strings() {
+        let facet = api::Facet::with_value("close", true);
+        assert_eq!((facet.val_type, facet.value), (Facet_ValType::BOOL, vec![1]));
+        assert_eq!(round_trip(false), false);
+
+        let facet = api::Facet::with_value("origin", "french");
+        assert_eq!((facet.val_type, facet.value), (Facet_ValType::STRING, b"french".to_vec()));
+        assert_eq!(round_trip("fran\u{e7}ais".to_string()), "fran\u{e7}ais");
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn encodes_datetimes() {
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let since = Utc.timestamp_opt(1_136_214_245, 0).unwrap();
+        let facet = api::Facet::with_value("since", since);
+        assert_eq!(facet.val_type, Facet_ValType::DATETIME);
+        assert_eq!(facet.value, vec![1, 0, 0, 0, 14, 187, 75, 55, 229, 0, 0, 0, 0, 255, 255]);
+        assert_eq!(facet.value_as::<DateTime<Utc>>().unwrap(), since);
+    }
+
+    #[test]
+    fn reports_type_mismatches() {
+        match api::Facet::with_value("since", 2006).value_as::<String>() {
+            Err(DgraphError::ValueTypeMismatch { expected: "STRING", found: "INT" }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+        match api::Facet::with_value("close", true).value_as::<f64>() {
+            Err(DgraphError::ValueTypeMismatch { expected: "FLOAT", found: "BOOL" }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[test]
+    fn reports_malformed_and_out_of_range_values() {
+        let mut facet = api::Facet::with_value("since", 2006);
+        facet.value.pop();
+        match facet.value_as::<i64>() {
+            Err(DgraphError::InvalidFacet(message)) => assert_eq!(message, "malformed INT facet \"since\""),
+            res => panic!("unexpected {:?}", res),
+        }
+
+        let mut facet = api::Facet::with_value("close", true);
+        facet.value = vec![2];
+        assert!(facet.value_as::<bool>().is_err());
+
+        match api::Facet::with_value("rank", -1).value_as::<u32>() {
+            Err(DgraphError::IntOutOfRange { value: -1, target: "u32" }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+        match api::Facet::with_value("weight", 0.1).value_as::<f32>() {
+            Err(DgraphError::FloatPrecisionLoss { target: "f32", .. }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn collects_facet_keys() {
+        let json = r#"{"name": "Alice", "name|origin": "french", "name@en|verified": true, "friend|since": 2006}"#;
+        let facets: Facets = serde_json::from_str(json).unwrap();
+
+        assert_eq!(facets.len(), 3);
+        assert_eq!(facets.get::<String>("name", "origin").unwrap(), Some("french".to_string()));
+        assert_eq!(facets.get::<bool>("name@en", "verified").unwrap(), Some(true));
+        assert_eq!(facets.get::<i64>("friend", "since").unwrap(), Some(2006));
+        assert_eq!(facets.get::<i64>("friend", "close").unwrap(), None);
+
+        match facets.get::<i64>("name", "origin") {
+            Err(DgraphError::JsonDecode { path, .. }) => assert_eq!(path, "$.name|origin"),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_facets() {
+        let mut facets = Facets::new();
+        facets.insert("name", "origin", "french").unwrap();
+        facets.insert("name@en", "verified", true).unwrap();
+
+        let json = serde_json::to_string(&facets).unwrap();
+        assert_eq!(json, r#"{"name@en|verified":true,"name|origin":"french"}"#);
+        assert_eq!(serde_json::from_str::<Facets>(&json).unwrap(), facets);
+
+        let keys: Vec<_> = facets.iter().map(|(predicate, facet, _)| (predicate, facet)).collect();
+        assert_eq!(keys, vec![("name@en", "verified"), ("name", "origin")]);
+    }
+}
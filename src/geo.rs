@@ -0,0 +1,561 @@
+use std::convert::TryInto;
+
+use crate::errors::DgraphError;
+use crate::protos::api;
+use crate::protos::api::Value_oneof_val::*;
+use crate::value::{mismatch, FromDgraphValue, ToDgraphValue};
+
+const WKB_LITTLE_ENDIAN: u8 = 1;
+const WKB_BIG_ENDIAN: u8 = 0;
+
+const WKB_POINT: u32 = 1;
+const WKB_POLYGON: u32 = 3;
+const WKB_MULTI_POLYGON: u32 = 6;
+
+/// A position in degrees, in the longitude, latitude order used by GeoJSON.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub longitude: f64,
+    pub latitude: f64,
+}
+
+/// A polygon made of an exterior ring followed by its holes, if any. Each ring
+/// is closed, its last point being the same as its first one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    pub rings: Vec<Vec<Point>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiPolygon {
+    pub polygons: Vec<Polygon>,
+}
+
+/// Any of the geometries stored by `geo` predicates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Geometry {
+    Point(Point),
+    Polygon(Polygon),
+    MultiPolygon(MultiPolygon),
+}
+
+impl Point {
+    pub fn new(longitude: f64, latitude: f64) -> Self {
+        Point {
+            longitude,
+            latitude,
+        }
+    }
+}
+
+impl Polygon {
+    pub fn new(rings: Vec<Vec<Point>>) -> Self {
+        Polygon { rings }
+    }
+}
+
+impl MultiPolygon {
+    pub fn new(polygons: Vec<Polygon>) -> Self {
+        MultiPolygon { polygons }
+    }
+}
+
+impl Geometry {
+    /// Encodes the geometry as little endian WKB, the format of `Value::geo_val`.
+    pub fn to_wkb(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_geometry(&mut out, self);
+        out
+    }
+
+    /// Decodes a WKB geometry, in either byte order.
+    pub fn from_wkb(bytes: &[u8]) -> Result<Self, DgraphError> {
+        let mut reader = WkbReader { bytes, pos: 0 };
+        let geometry = reader.geometry()?;
+
+        if reader.pos != bytes.len() {
+            return Err(DgraphError::InvalidGeo("trailing bytes after the geometry".to_string()));
+        }
+
+        Ok(geometry)
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Geometry::Point(_) => "Point",
+            Geometry::Polygon(_) => "Polygon",
+            Geometry::MultiPolygon(_) => "MultiPolygon",
+        }
+    }
+}
+
+impl From<Point> for Geometry {
+    fn from(point: Point) -> Self {
+        Geometry::Point(point)
+    }
+}
+
+impl From<Polygon> for Geometry {
+    fn from(polygon: Polygon) -> Self {
+        Geometry::Polygon(polygon)
+    }
+}
+
+impl From<MultiPolygon> for Geometry {
+    fn from(multi_polygon: MultiPolygon) -> Self {
+        Geometry::MultiPolygon(multi_polygon)
+    }
+}
+
+fn write_header(out: &mut Vec<u8>, kind: u32) {
+    out.push(WKB_LITTLE_ENDIAN);
+    out.extend_from_slice(&kind.to_le_bytes());
+}
+
+fn write_points(out: &mut Vec<u8>, points: &[Point]) {
+    out.extend_from_slice(&(points.len() as u32).to_le_bytes());
+    for point in points {
+        out.extend_from_slice(&point.longitude.to_bits().to_le_bytes());
+        out.extend_from_slice(&point.latitude.to_bits().to_le_bytes());
+    }
+}
+
+fn write_polygon(out: &mut Vec<u8>, polygon: &Polygon) {
+    write_header(out, WKB_POLYGON);
+    out.extend_from_slice(&(polygon.rings.len() as u32).to_le_bytes());
+    for ring in polygon.rings.iter() {
+        write_points(out, ring);
+    }
+}
+
+fn write_geometry(out: &mut Vec<u8>, geometry: &Geometry) {
+    match geometry {
+        Geometry::Point(point) => {
+            write_header(out, WKB_POINT);
+            out.extend_from_slice(&point.longitude.to_bits().to_le_bytes());
+            out.extend_from_slice(&point.latitude.to_bits().to_le_bytes());
+        }
+        Geometry::Polygon(polygon) => write_polygon(out, polygon),
+        Geometry::MultiPolygon(multi_polygon) => {
+            write_header(out, WKB_MULTI_POLYGON);
+            out.extend_from_slice(&(multi_polygon.polygons.len() as u32).to_le_bytes());
+            for polygon in multi_polygon.polygons.iter() {
+                write_polygon(out, polygon);
+            }
+        }
+    }
+}
+
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WkbReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DgraphError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(DgraphError::InvalidGeo("unexpected end of the geometry".to_string()));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32, DgraphError> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn point(&mut self, little_endian: bool) -> Result<Point, DgraphError> {
+        let mut coordinate = || -> Result<f64, DgraphError> {
+            let bytes = self.take(8)?.try_into().unwrap();
+            Ok(f64::from_bits(if little_endian {
+                u64::from_le_bytes(bytes)
+            } else {
+                u64::from_be_bytes(bytes)
+            }))
+        };
+        let longitude = coordinate()?;
+        let latitude = coordinate()?;
+        Ok(Point::new(longitude, latitude))
+    }
+
+    /// Reads a count of items, each at least `item_len` bytes long. Checking it
+    /// against the remaining bytes keeps corrupted counts from allocating.
+    fn count(&mut self, little_endian: bool, item_len: usize) -> Result<usize, DgraphError> {
+        let count = self.u32(little_endian)? as usize;
+        if count.saturating_mul(item_len) > self.bytes.len() - self.pos {
+            return Err(DgraphError::InvalidGeo("unexpected end of the geometry".to_string()));
+        }
+        Ok(count)
+    }
+
+    fn polygon(&mut self, little_endian: bool) -> Result<Polygon, DgraphError> {
+        let ring_count = self.count(little_endian, 4)?;
+        let mut rings = Vec::with_capacity(ring_count);
+        for _ in 0..ring_count {
+            let point_count = self.count(little_endian, 16)?;
+            let mut ring = Vec::with_capacity(point_count);
+            for _ in 0..point_count {
+                ring.push(self.point(little_endian)?);
+            }
+            rings.push(ring);
+        }
+        Ok(Polygon::new(rings))
+    }
+
+    /// Reads the byte order and the type of the next geometry.
+    fn header(&mut self) -> Result<(bool, u32), DgraphError> {
+        let little_endian = match self.take(1)?[0] {
+            WKB_LITTLE_ENDIAN => true,
+            WKB_BIG_ENDIAN => false,
+            order => return Err(DgraphError::InvalidGeo(format!("invalid byte order {}", order))),
+        };
+        Ok((little_endian, self.u32(little_endian)?))
+    }
+
+    fn geometry(&mut self) -> Result<Geometry, DgraphError> {
+        let (little_endian, kind) = self.header()?;
+
+        match kind {
+            WKB_POINT => Ok(Geometry::Point(self.point(little_endian)?)),
+            WKB_POLYGON => Ok(Geometry::Polygon(self.polygon(little_endian)?)),
+            WKB_MULTI_POLYGON => {
+                let polygon_count = self.count(little_endian, 9)?;
+                let mut polygons = Vec::with_capacity(polygon_count);
+                for _ in 0..polygon_count {
+                    match self.header()? {
+                        (little_endian, WKB_POLYGON) => polygons.push(self.polygon(little_endian)?),
+                        (_, kind) => {
+                            return Err(DgraphError::InvalidGeo(format!(
+                                "unexpected geometry type {} in a MultiPolygon",
+                                kind
+                            )));
+                        }
+                    }
+                }
+                Ok(Geometry::MultiPolygon(MultiPolygon::new(polygons)))
+            }
+            kind => Err(DgraphError::InvalidGeo(format!("unsupported geometry type {}", kind))),
+        }
+    }
+}
+
+impl ToDgraphValue for Geometry {
+    fn to_dgraph_value(&self) -> api::Value {
+        let mut value = api::Value::new();
+        value.set_geo_val(self.to_wkb());
+        value
+    }
+}
+
+impl FromDgraphValue for Geometry {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(geo_val(ref bytes)) => Geometry::from_wkb(bytes),
+            _ => Err(mismatch("geo_val", value)),
+        }
+    }
+}
+
+impl From<Geometry> for api::Value {
+    fn from(geometry: Geometry) -> Self {
+        geometry.to_dgraph_value()
+    }
+}
+
+macro_rules! geometry_value {
+    ($($ty:ident),*) => {
+        $(
+            impl ToDgraphValue for $ty {
+                fn to_dgraph_value(&self) -> api::Value {
+                    Geometry::$ty(self.clone()).to_dgraph_value()
+                }
+            }
+
+            impl FromDgraphValue for $ty {
+                fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+                    match Geometry::from_dgraph_value(value)? {
+                        Geometry::$ty(geometry) => Ok(geometry),
+                        other => Err(DgraphError::InvalidGeo(format!(
+                            "expected a {}, found a {}",
+                            stringify!($ty),
+                            other.kind()
+                        ))),
+                    }
+                }
+            }
+
+            impl From<$ty> for api::Value {
+                fn from(geometry: $ty) -> Self {
+                    geometry.to_dgraph_value()
+                }
+            }
+        )*
+    };
+}
+
+geometry_value!(Point, Polygon, MultiPolygon);
+
+/// Geometries are serialized as GeoJSON, the format used for `geo` predicates
+/// in JSON mutations and query responses.
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::{Serialize, SerializeMap, Serializer};
+    use serde_json::Value;
+
+    use super::{Geometry, MultiPolygon, Point, Polygon};
+
+    fn position(point: &Point) -> [f64; 2] {
+        [point.longitude, point.latitude]
+    }
+
+    fn ring(points: &[Point]) -> Vec<[f64; 2]> {
+        points.iter().map(position).collect()
+    }
+
+    fn rings(polygon: &Polygon) -> Vec<Vec<[f64; 2]>> {
+        polygon.rings.iter().map(|points| ring(points)).collect()
+    }
+
+    fn serialize_geojson<S, T>(serializer: S, kind: &str, coordinates: &T) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("type", kind)?;
+        map.serialize_entry("coordinates", coordinates)?;
+        map.end()
+    }
+
+    impl Serialize for Geometry {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Geometry::Point(point) => point.serialize(serializer),
+                Geometry::Polygon(polygon) => polygon.serialize(serializer),
+                Geometry::MultiPolygon(multi_polygon) => multi_polygon.serialize(serializer),
+            }
+        }
+    }
+
+    impl Serialize for Point {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_geojson(serializer, "Point", &position(self))
+        }
+    }
+
+    impl Serialize for Polygon {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_geojson(serializer, "Polygon", &rings(self))
+        }
+    }
+
+    impl Serialize for MultiPolygon {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let polygons: Vec<_> = self.polygons.iter().map(rings).collect();
+            serialize_geojson(serializer, "MultiPolygon", &polygons)
+        }
+    }
+
+    fn array<'a>(json: &'a Value, what: &str) -> Result<&'a Vec<Value>, String> {
+        json.as_array()
+            .ok_or_else(|| format!("expected an array of {}, found {}", what, json))
+    }
+
+    fn point_from_json(json: &Value) -> Result<Point, String> {
+        let position = array(json, "coordinates")?;
+        match (position.first().and_then(Value::as_f64), position.get(1).and_then(Value::as_f64)) {
+            (Some(longitude), Some(latitude)) => Ok(Point::new(longitude, latitude)),
+            _ => Err(format!("invalid position {}", json)),
+        }
+    }
+
+    fn polygon_from_json(json: &Value) -> Result<Polygon, String> {
+        let rings = array(json, "rings")?
+            .iter()
+            .map(|ring| array(ring, "positions")?.iter().map(point_from_json).collect())
+            .collect::<Result<_, _>>()?;
+        Ok(Polygon::new(rings))
+    }
+
+    fn geometry_from_json(json: &Value) -> Result<Geometry, String> {
+        let coordinates = json
+            .get("coordinates")
+            .ok_or_else(|| "missing GeoJSON coordinates".to_string())?;
+
+        match json.get("type").and_then(Value::as_str) {
+            Some("Point") => Ok(Geometry::Point(point_from_json(coordinates)?)),
+            Some("Polygon") => Ok(Geometry::Polygon(polygon_from_json(coordinates)?)),
+            Some("MultiPolygon") => {
+                let polygons = array(coordinates, "polygons")?
+                    .iter()
+                    .map(polygon_from_json)
+                    .collect::<Result<_, _>>()?;
+                Ok(Geometry::MultiPolygon(MultiPolygon::new(polygons)))
+            }
+            Some(kind) => Err(format!("unsupported GeoJSON type {:?}", kind)),
+            None => Err("missing GeoJSON type".to_string()),
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Geometry {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = Value::deserialize(deserializer)?;
+            geometry_from_json(&json).map_err(de::Error::custom)
+        }
+    }
+
+    macro_rules! deserialize_geometry {
+        ($($ty:ident),*) => {
+            $(
+                impl<'de> Deserialize<'de> for $ty {
+                    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                        match Geometry::deserialize(deserializer)? {
+                            Geometry::$ty(geometry) => Ok(geometry),
+                            other => Err(de::Error::custom(format!(
+                                "expected a {}, found a {}",
+                                stringify!($ty),
+                                other.kind()
+                            ))),
+                        }
+                    }
+                }
+            )*
+        };
+    }
+
+    deserialize_geometry!(Point, Polygon, MultiPolygon);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+    const ONE: [u8; 8] = [0, 0, 0, 0, 0, 0, 240, 63];
+
+    fn triangle() -> Polygon {
+        Polygon::new(vec![vec![
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(0.0, 1.0),
+            Point::new(0.0, 0.0),
+        ]])
+    }
+
+    /// Little endian WKB of `triangle()`.
+    fn triangle_wkb() -> Vec<u8> {
+        [
+            &[1, 3, 0, 0, 0][..],
+            &[1, 0, 0, 0],
+            &[4, 0, 0, 0],
+            &ZERO, &ZERO,
+            &ONE, &ZERO,
+            &ZERO, &ONE,
+            &ZERO, &ZERO,
+        ]
+        .concat()
+    }
+
+    fn invalid(bytes: &[u8]) -> String {
+        match Geometry::from_wkb(bytes) {
+            Err(DgraphError::InvalidGeo(message)) => message,
+            res => panic!("expected an invalid geometry, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn encodes_points() {
+        let point = Geometry::Point(Point::new(1.5, -2.0));
+        let wkb = [1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 248, 63, 0, 0, 0, 0, 0, 0, 0, 192];
+        assert_eq!(point.to_wkb(), wkb);
+        assert_eq!(Geometry::from_wkb(&wkb).unwrap(), point);
+    }
+
+    #[test]
+    fn encodes_polygons() {
+        let polygon = Geometry::Polygon(triangle());
+        assert_eq!(polygon.to_wkb(), triangle_wkb());
+        assert_eq!(Geometry::from_wkb(&triangle_wkb()).unwrap(), polygon);
+    }
+
+    #[test]
+    fn encodes_multi_polygons() {
+        let multi_polygon = Geometry::MultiPolygon(MultiPolygon::new(vec![triangle(), triangle()]));
+        let wkb = [&[1, 6, 0, 0, 0][..], &[2, 0, 0, 0], &triangle_wkb(), &triangle_wkb()].concat();
+        assert_eq!(multi_polygon.to_wkb(), wkb);
+        assert_eq!(Geometry::from_wkb(&wkb).unwrap(), multi_polygon);
+    }
+
+    #[test]
+    fn decodes_big_endian() {
+        let wkb = [0, 0, 0, 0, 1, 63, 248, 0, 0, 0, 0, 0, 0, 192, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(Geometry::from_wkb(&wkb).unwrap(), Geometry::Point(Point::new(1.5, -2.0)));
+    }
+
+    #[test]
+    fn rejects_malformed_wkb() {
+        assert_eq!(invalid(&[]), "unexpected end of the geometry");
+        assert_eq!(invalid(&[2, 1, 0, 0, 0]), "invalid byte order 2");
+
+        let point = Geometry::Point(Point::new(1.5, -2.0)).to_wkb();
+        assert_eq!(invalid(&point[..point.len() - 1]), "unexpected end of the geometry");
+        assert_eq!(invalid(&[&point[..], &[0]].concat()), "trailing bytes after the geometry");
+
+        let line_string = [&[1, 2, 0, 0, 0][..], &[0, 0, 0, 0]].concat();
+        assert_eq!(invalid(&line_string), "unsupported geometry type 2");
+
+        let multi_point = [&[1, 6, 0, 0, 0][..], &[1, 0, 0, 0], &point].concat();
+        assert_eq!(invalid(&multi_point), "unexpected geometry type 1 in a MultiPolygon");
+
+        let huge_count = [&[1, 3, 0, 0, 0][..], &[255, 255, 255, 255]].concat();
+        assert_eq!(invalid(&huge_count), "unexpected end of the geometry");
+    }
+
+    #[test]
+    fn checks_the_geometry_type_of_values() {
+        let value = triangle().to_dgraph_value();
+        assert_eq!(Polygon::from_dgraph_value(&value).unwrap(), triangle());
+        match Point::from_dgraph_value(&value) {
+            Err(DgraphError::InvalidGeo(message)) => assert_eq!(message, "expected a Point, found a Polygon"),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_geojson() {
+        let point = Geometry::Point(Point::new(1.5, -2.0));
+        let json = serde_json::to_string(&point).unwrap();
+        assert_eq!(json, r#"{"type":"Point","coordinates":[1.5,-2.0]}"#);
+        assert_eq!(serde_json::from_str::<Geometry>(&json).unwrap(), point);
+
+        let polygon = Geometry::Polygon(triangle());
+        let json = serde_json::to_string(&polygon).unwrap();
+        assert_eq!(json, r#"{"type":"Polygon","coordinates":[[[0.0,0.0],[1.0,0.0],[0.0,1.0],[0.0,0.0]]]}"#);
+        assert_eq!(serde_json::from_str::<Geometry>(&json).unwrap(), polygon);
+
+        let multi_polygon = Geometry::MultiPolygon(MultiPolygon::new(vec![triangle()]));
+        let json = serde_json::to_string(&multi_polygon).unwrap();
+        assert_eq!(json, r#"{"type":"MultiPolygon","coordinates":[[[[0.0,0.0],[1.0,0.0],[0.0,1.0],[0.0,0.0]]]]}"#);
+        assert_eq!(serde_json::from_str::<Geometry>(&json).unwrap(), multi_polygon);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn rejects_invalid_geojson() {
+        let error = |json: &str| serde_json::from_str::<Geometry>(json).unwrap_err().to_string();
+
+        assert_eq!(error(r#"{"type":"LineString","coordinates":[]}"#), r#"unsupported GeoJSON type "LineString""#);
+        assert_eq!(error(r#"{"type":"Point"}"#), "missing GeoJSON coordinates");
+        assert_eq!(error(r#"{"coordinates":[1,2]}"#), "missing GeoJSON type");
+        assert_eq!(error(r#"{"type":"Point","coordinates":[1]}"#), "invalid position [1]");
+        assert!(serde_json::from_str::<Point>(r#"{"type":"Polygon","coordinates":[]}"#).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::assigned::AssignedUids;
+use crate::async_txn::AsyncTxn;
+use crate::errors::DgraphError;
+use crate::protos::api;
+use crate::query::Vars;
+use crate::txn::Txn;
+use crate::uid::Uid;
+
+/// Query response with the JSON payload decoded into `T`.
+#[derive(Debug)]
+pub struct QueryResponse<T> {
+    pub data: T,
+    pub latency: api::Latency,
+    pub txn: api::TxnContext,
+}
+
+impl<T: DeserializeOwned> QueryResponse<T> {
+    /// Decodes the JSON payload of a raw query response.
+    pub fn from_response(mut res: api::Response) -> Result<Self, DgraphError> {
+        let data = serde_json::from_slice(&res.json).map_err(|err| DgraphError::JsonDecode {
+            path: json_path_at(&res.json, err.line(), err.column()),
+            err,
+        })?;
+
+        Ok(QueryResponse {
+            data,
+            latency: res.take_latency(),
+            txn: res.take_txn(),
+        })
+    }
+}
+
+impl Txn {
+    /// Runs the query and decodes the JSON response into `T`.
+    pub fn query_as<T: DeserializeOwned>(&mut self, query: impl Into<String>) -> Result<QueryResponse<T>, DgraphError> {
+        self.query_with_vars_as(query, HashMap::new())
+    }
+
+    pub fn query_with_vars_as<T: DeserializeOwned>(&mut self, query: impl Into<String>, vars: HashMap<String, String>) -> Result<QueryResponse<T>, DgraphError> {
+        QueryResponse::from_response(self.query_with_vars(query, vars)?)
+    }
+
+    pub fn query_with_typed_vars_as<T: DeserializeOwned>(&mut self, query: impl Into<String>, vars: &Vars) -> Result<QueryResponse<T>, DgraphError> {
+        QueryResponse::from_response(self.query_with_typed_vars(query, vars)?)
+    }
+
+    /// Sets the value as JSON and returns the uids assigned to its blank nodes.
+    pub fn set_json<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, false, false)?)?;
+        assigned_uids(&assigned, false)
+    }
+
+    /// Same as `set_json`, but commits the transaction immediately.
+    pub fn set_json_commit_now<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, false, true)?)?;
+        assigned_uids(&assigned, true)
+    }
+
+    /// Deletes the edges described by the value serialized as JSON.
+    pub fn delete_json<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, true, false)?)?;
+        assigned_uids(&assigned, false)
+    }
+
+    /// Same as `delete_json`, but commits the transaction immediately.
+    pub fn delete_json_commit_now<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, true, true)?)?;
+        assigned_uids(&assigned, true)
+    }
+}
+
+impl AsyncTxn {
+    /// Runs the query and decodes the JSON response into `T`.
+    pub async fn query_as<T: DeserializeOwned>(&mut self, query: impl Into<String>) -> Result<QueryResponse<T>, DgraphError> {
+        self.query_with_vars_as(query, HashMap::new()).await
+    }
+
+    pub async fn query_with_vars_as<T: DeserializeOwned>(&mut self, query: impl Into<String>, vars: HashMap<String, String>) -> Result<QueryResponse<T>, DgraphError> {
+        QueryResponse::from_response(self.query_with_vars(query, vars).await?)
+    }
+
+    pub async fn query_with_typed_vars_as<T: DeserializeOwned>(&mut self, query: impl Into<String>, vars: &Vars) -> Result<QueryResponse<T>, DgraphError> {
+        QueryResponse::from_response(self.query_with_typed_vars(query, vars).await?)
+    }
+
+    /// Sets the value as JSON and returns the uids assigned to its blank nodes.
+    pub async fn set_json<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, false, false)?).await?;
+        assigned_uids(&assigned, false)
+    }
+
+    /// Same as `set_json`, but commits the transaction immediately.
+    pub async fn set_json_commit_now<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, false, true)?).await?;
+        assigned_uids(&assigned, true)
+    }
+
+    /// Deletes the edges described by the value serialized as JSON.
+    pub async fn delete_json<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, true, false)?).await?;
+        assigned_uids(&assigned, false)
+    }
+
+    /// Same as `delete_json`, but commits the transaction immediately.
+    pub async fn delete_json_commit_now<T: Serialize>(&mut self, value: &T) -> Result<AssignedUids, DgraphError> {
+        let assigned = self.mutate(json_mutation(value, true, true)?).await?;
+        assigned_uids(&assigned, true)
+    }
+}
+
+fn json_mutation<T: Serialize>(value: &T, delete: bool, commit_now: bool) -> Result<api::Mutation, DgraphError> {
+    let json = serde_json::to_vec(value).map_err(DgraphError::JsonEncode)?;
+
+    let mut mu = api::Mutation {
+        commit_now,
+        ..Default::default()
+    };
+    if delete {
+        mu.delete_json = json;
+    } else {
+        mu.set_json = json;
+    }
+
+    Ok(mu)
+}
+
+/// Reads the uids assigned by a JSON mutation. A mutation sent with
+/// `commit_now` is committed even if one of its uids cannot be read, which is
+/// reported with `DgraphError::CommittedWithInvalidUid` so that it is not
+/// mistaken for a failed mutation.
+fn assigned_uids(assigned: &api::Assigned, commit_now: bool) -> Result<AssignedUids, DgraphError> {
+    AssignedUids::from_assigned(assigned).map_err(|err| match err {
+        DgraphError::InvalidUid(uid) if commit_now => DgraphError::CommittedWithInvalidUid(uid),
+        err => err,
+    })
+}
+
+impl AssignedUids {
+    /// Resolves the uids of the top-level objects of a value submitted with
+    /// `set_json`, so that the uid of the object at position `i` of a submitted
+    /// array is found at index `i`. A single object yields a single entry.
+    ///
+    /// Objects without a `uid` field get the implicit blank node names `blank-N`,
+    /// numbered depth first with each object numbered before its children. Only
+    /// the numbering of top-level objects is deterministic, since the server
+    /// visits the fields of an object in no particular order.
+    pub fn json_uids<T: Serialize>(&self, value: &T) -> Result<Vec<Option<Uid>>, DgraphError> {
+        let objects = match serde_json::to_value(value).map_err(DgraphError::JsonEncode)? {
+            Value::Array(objects) => objects,
+            value => vec![value],
+        };
+
+        let mut next_blank = 0;
+        let uids = objects
+            .iter()
+            .map(|object| {
+                let uid = match explicit_uid(object) {
+                    Some(uid) => self.get(uid).or_else(|| uid.parse().ok()),
+                    None if is_node(object) => self.get(&format!("blank-{}", next_blank)),
+                    None => None,
+                };
+                next_blank += implicit_blank_nodes(object);
+                uid
+            })
+            .collect();
+
+        Ok(uids)
+    }
+}
+
+fn explicit_uid(value: &Value) -> Option<&str> {
+    value.get("uid").and_then(Value::as_str).filter(|uid| !uid.is_empty())
+}
+
+/// Objects holding only `type` and `coordinates` are geo values, not nodes.
+fn is_node(value: &Value) -> bool {
+    match value.as_object() {
+        Some(object) => !(object.len() == 2 && object.contains_key("type") && object.contains_key("coordinates")),
+        None => false,
+    }
+}
+
+/// Counts the objects the server names `blank-N` when the value is set.
+fn implicit_blank_nodes(value: &Value) -> usize {
+    match value {
+        Value::Object(object) if is_node(value) => {
+            let own = if explicit_uid(value).is_none() { 1 } else { 0 };
+            own + object.values().map(implicit_blank_nodes).sum::<usize>()
+        }
+        Value::Array(values) => values.iter().map(implicit_blank_nodes).sum(),
+        _ => 0,
+    }
+}
+
+enum Frame {
+    Object { key: Option<String>, expecting_key: bool },
+    Array { index: usize },
+}
+
+/// Returns the path, like `$.me[0].friend[1].age`, of the JSON value found at
+/// the line and column reported by a serde_json error.
+fn json_path_at(json: &[u8], line: usize, column: usize) -> String {
+    let line_start: usize = json
+        .split(|&b| b == b'\n')
+        .take(line.saturating_sub(1))
+        .map(|l| l.len() + 1)
+        .sum();
+    // serde_json reports the column of the last consumed byte.
+    let offset = (line_start + column).min(json.len());
+
+    let mut stack = Vec::new();
+    let mut i = 0;
+    while i < offset {
+        match json[i] {
+            b'"' => {
+                let start = i + 1;
+                i += 1;
+                while i < json.len() && json[i] != b'"' {
+                    if json[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                if let Some(Frame::Object { key, expecting_key: true }) = stack.last_mut() {
+                    *key = Some(String::from_utf8_lossy(&json[start..i.min(json.len())]).into_owned());
+                }
+            }
+            b'{' => stack.push(Frame::Object { key: None, expecting_key: true }),
+            b'[' => stack.push(Frame::Array { index: 0 }),
+            b'}' | b']' => {
+                stack.pop();
+            }
+            b':' => {
+                if let Some(Frame::Object { expecting_key, .. }) = stack.last_mut() {
+                    *expecting_key = false;
+                }
+            }
+            b',' => match stack.last_mut() {
+                Some(Frame::Object { expecting_key, .. }) => *expecting_key = true,
+                Some(Frame::Array { index }) => *index += 1,
+                None => (),
+            },
+            _ => (),
+        }
+        i += 1;
+    }
+
+    let mut path = String::from("$");
+    for frame in stack {
+        match frame {
+            Frame::Object { key: Some(key), .. } => {
+                path.push('.');
+                path.push_str(&key);
+            }
+            Frame::Object { key: None, .. } => (),
+            Frame::Array { index } => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Root {
+        me: Vec<Person>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Person {
+        name: String,
+        age: u8,
+        #[serde(default)]
+        friend: Vec<Person>,
+    }
+
+    fn error_path(json: &str) -> String {
+        let res = api::Response {
+            json: json.as_bytes().to_vec(),
+            ..Default::default()
+        };
+        match QueryResponse::<Root>::from_response(res) {
+            Err(DgraphError::JsonDecode { path, .. }) => path,
+            res => panic!("expected a decode error, got {:?}", res.map(|res| res.data)),
+        }
+    }
+
+    fn assigned(uids: &[(&str, &str)]) -> AssignedUids {
+        let assigned = api::Assigned {
+            uids: uids.iter().map(|&(name, uid)| (name.to_string(), uid.to_string())).collect(),
+            ..Default::default()
+        };
+        AssignedUids::from_assigned(&assigned).unwrap()
+    }
+
+    fn uids(expected: &[Option<u64>]) -> Vec<Option<Uid>> {
+        expected.iter().map(|uid| uid.map(Uid::new)).collect()
+    }
+
+    #[test]
+    fn numbers_top_level_objects() {
+        let assigned = assigned(&[("blank-0", "0x1"), ("blank-1", "0x2")]);
+        let value = json!([{"name": "Alice"}, {"name": "Bob"}]);
+        assert_eq!(assigned.json_uids(&value).unwrap(), uids(&[Some(1), Some(2)]));
+
+        let value = json!({"name": "Alice"});
+        assert_eq!(assigned.json_uids(&value).unwrap(), uids(&[Some(1)]));
+    }
+
+    #[test]
+    fn numbers_nested_objects_depth_first() {
+        let assigned = assigned(&[("blank-0", "0x1"), ("blank-1", "0x2"), ("blank-2", "0x3")]);
+        let value = json!([{"name": "Alice", "friend": {"name": "Bob"}}, {"name": "Carol"}]);
+        assert_eq!(assigned.json_uids(&value).unwrap(), uids(&[Some(1), Some(3)]));
+    }
+
+    #[test]
+    fn numbers_arrays_of_objects() {
+        let assigned = assigned(&[("blank-0", "0x1"), ("blank-1", "0x2"), ("blank-2", "0x3"), ("blank-3", "0x4")]);
+        let value = json!([{"name": "Alice", "friend": [{"name": "Bob"}, {"name": "Carol"}]}, {"name": "Dave"}]);
+        assert_eq!(assigned.json_uids(&value).unwrap(), uids(&[Some(1), Some(4)]));
+    }
+
+    #[test]
+    fn explicit_uids_do_not_consume_blank_names() {
+        let assigned = assigned(&[("alice", "0xa"), ("blank-0", "0x1"), ("blank-1", "0x2")]);
+        let value = json!([
+            {"uid": "_:alice", "name": "Alice", "friend": {"name": "Bob"}},
+            {"uid": "0x5", "friend": {"uid": "0x6"}},
+            {"name": "Carol"},
+        ]);
+        assert_eq!(assigned.json_uids(&value).unwrap(), uids(&[Some(10), Some(5), Some(2)]));
+    }
+
+    #[test]
+    fn geo_values_are_not_nodes() {
+        let assigned = assigned(&[("blank-0", "0x1"), ("blank-1", "0x2")]);
+        let value = json!([
+            {"name": "Alice", "loc": {"type": "Point", "coordinates": [1.0, 2.0]}},
+            {"name": "Bob"},
+        ]);
+        assert_eq!(assigned.json_uids(&value).unwrap(), uids(&[Some(1), Some(2)]));
+    }
+
+    #[test]
+    fn non_objects_have_no_uid() {
+        let assigned = assigned(&[("blank-0", "0x1")]);
+        assert_eq!(assigned.json_uids(&json!([1, {"name": "Alice"}])).unwrap(), uids(&[None, Some(1)]));
+    }
+
+    #[test]
+    fn set_mutations_fill_set_json() {
+        let mu = json_mutation(&json!({"name": "Alice"}), false, false).unwrap();
+        assert_eq!(mu.set_json, br#"{"name":"Alice"}"#.to_vec());
+        assert!(mu.delete_json.is_empty());
+        assert!(!mu.commit_now);
+    }
+
+    #[test]
+    fn delete_mutations_fill_delete_json() {
+        let mu = json_mutation(&json!({"uid": "0x1"}), true, false).unwrap();
+        assert_eq!(mu.delete_json, br#"{"uid":"0x1"}"#.to_vec());
+        assert!(mu.set_json.is_empty());
+        assert!(!mu.commit_now);
+    }
+
+    #[test]
+    fn passes_commit_now_through() {
+        assert!(json_mutation(&json!({"name": "Alice"}), false, true).unwrap().commit_now);
+        assert!(json_mutation(&json!({"uid": "0x1"}), true, true).unwrap().commit_now);
+    }
+
+    #[test]
+    fn reports_invalid_uids_of_committed_mutations_apart() {
+        let mut assigned = api::Assigned::new();
+        assigned.uids.insert("blank-0".to_string(), "0x1".to_string());
+        assert_eq!(assigned_uids(&assigned, true).unwrap().get("blank-0"), Some(Uid::new(1)));
+
+        assigned.uids.insert("blank-1".to_string(), "bogus".to_string());
+        match assigned_uids(&assigned, false) {
+            Err(DgraphError::InvalidUid(uid)) => assert_eq!(uid, "bogus"),
+            res => panic!("unexpected result {:?}", res),
+        }
+        match assigned_uids(&assigned, true) {
+            Err(DgraphError::CommittedWithInvalidUid(uid)) => assert_eq!(uid, "bogus"),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn decodes_valid_responses() {
+        let res = api::Response {
+            json: br#"{"me": [{"name": "Alice", "age": 26}]}"#.to_vec(),
+            ..Default::default()
+        };
+        let res = QueryResponse::<Root>::from_response(res).unwrap();
+        assert_eq!(res.data.me[0].name, "Alice");
+    }
+
+    #[test]
+    fn reports_the_path_of_a_mistyped_value() {
+        let json = r#"{"me": [{"name": "Alice", "age": 26}, {"name": "Bob", "age": "old"}]}"#;
+        assert_eq!(error_path(json), "$.me[1].age");
+    }
+
+    #[test]
+    fn reports_the_path_in_nested_arrays() {
+        let json = r#"{"me": [{"name": "Alice", "age": 26, "friend": [{"name": "Bob", "age": 1}, {"name": 2, "age": 3}]}]}"#;
+        assert_eq!(error_path(json), "$.me[0].friend[1].name");
+    }
+
+    #[test]
+    fn reports_the_object_missing_a_field() {
+        let json = r#"{"me": [{"name": "Alice", "age": 26}, {"name": "Bob"}]}"#;
+        assert_eq!(error_path(json), "$.me[1]");
+    }
+
+    #[test]
+    fn reports_the_path_in_multiline_json() {
+        let json = "{\n  \"me\": [\n    {\"name\": \"Alice\", \"age\": 26},\n    {\"name\": \"Bob\",\n     \"age\": 300}\n  ]\n}";
+        assert_eq!(error_path(json), "$.me[1].age");
+    }
+
+    #[test]
+    fn skips_escaped_quotes_in_strings() {
+        let json = r#"{"me": [{"name": "Al\"ice, \"age\": [", "age": -1}]}"#;
+        assert_eq!(error_path(json), "$.me[0].age");
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+
+use crate::nquad::NQuadBuilder;
+use crate::protos::api;
+use crate::protos::api::Value_oneof_val::*;
+
+/// The values of a string predicate in several languages, keyed by language
+/// tag. The empty tag holds the untagged value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LangString {
+    values: BTreeMap<String, String>,
+}
+
+impl LangString {
+    pub fn new() -> Self {
+        LangString::default()
+    }
+
+    /// Adds the value in the given language, `""` being the untagged value.
+    pub fn with(mut self, lang: impl Into<String>, text: impl Into<String>) -> Self {
+        self.insert(lang, text);
+        self
+    }
+
+    pub fn insert(&mut self, lang: impl Into<String>, text: impl Into<String>) -> Option<String> {
+        self.values.insert(lang.into(), text.into())
+    }
+
+    pub fn get(&self, lang: &str) -> Option<&str> {
+        self.values.get(lang).map(String::as_str)
+    }
+
+    /// Iterates over the values as `(lang, text)`, ordered by language tag.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.values.iter(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Builds one NQuad per language from a builder holding the subject and
+    /// the predicate, like `<subject> <predicate> "text"@lang .`.
+    pub fn to_nquads(&self, builder: NQuadBuilder) -> Vec<api::NQuad> {
+        self.iter()
+            .map(|(lang, text)| builder.clone().object_value(text).lang(lang).build())
+            .collect()
+    }
+
+    /// Collects the string values of the NQuads with their language tags.
+    /// NQuads holding other kinds of values are skipped.
+    pub fn from_nquads<'a>(nquads: impl IntoIterator<Item = &'a api::NQuad>) -> Self {
+        let mut lang_string = LangString::new();
+        for nquad in nquads {
+            match nquad.object_value.as_ref().and_then(|value| value.val.as_ref()) {
+                Some(str_val(text)) | Some(default_val(text)) => {
+                    lang_string.insert(nquad.lang.as_str(), text.as_str());
+                }
+                _ => (),
+            }
+        }
+        lang_string
+    }
+}
+
+pub struct Iter<'a> {
+    inner: btree_map::Iter<'a, String, String>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(lang, text)| (lang.as_str(), text.as_str()))
+    }
+}
+
+impl<'a> IntoIterator for &'a LangString {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Language tagged predicates of a JSON query response or mutation, which
+/// Dgraph writes as `predicate@lang` keys. Untagged values are written as the
+/// plain `predicate` key, and read back as the `""` language of predicates
+/// that also have tagged values. A predicate with only an untagged value
+/// cannot be told apart from other predicates, and is not read back. Meant to
+/// be flattened into the struct of the node:
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Person {
+///     #[serde(flatten)]
+///     langs: dgraph::LangStrings,
+/// }
+///
+/// let alice: Person = serde_json::from_str(r#"{"name": "Alice", "name@fr": "Alicia"}"#).unwrap();
+/// let name = alice.langs.get("name").unwrap();
+/// assert_eq!(name.get("fr"), Some("Alicia"));
+/// assert_eq!(name.get(""), Some("Alice"));
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LangStrings {
+    predicates: BTreeMap<String, LangString>,
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::fmt;
+    use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+    use serde::ser::{Serialize, SerializeMap, Serializer};
+    use serde_json::Value;
+
+    use super::{LangString, LangStrings};
+
+    impl LangStrings {
+        pub fn new() -> Self {
+            LangStrings::default()
+        }
+
+        pub fn get(&self, predicate: &str) -> Option<&LangString> {
+            self.predicates.get(predicate)
+        }
+
+        pub fn insert(&mut self, predicate: impl Into<String>, values: LangString) -> Option<LangString> {
+            self.predicates.insert(predicate.into(), values)
+        }
+
+        /// Iterates over the predicates and their values.
+        pub fn iter(&self) -> impl Iterator<Item = (&str, &LangString)> {
+            self.predicates.iter().map(|(predicate, values)| (predicate.as_str(), values))
+        }
+
+        pub fn len(&self) -> usize {
+            self.predicates.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.predicates.is_empty()
+        }
+    }
+
+    impl Serialize for LangStrings {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(None)?;
+            for (predicate, values) in self.predicates.iter() {
+                for (lang, text) in values {
+                    if lang.is_empty() {
+                        map.serialize_entry(predicate, text)?;
+                    } else {
+                        map.serialize_entry(&format!("{}@{}", predicate, lang), text)?;
+                    }
+                }
+            }
+            map.end()
+        }
+    }
+
+    struct LangStringsVisitor;
+
+    impl<'de> Visitor<'de> for LangStringsVisitor {
+        type Value = LangStrings;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map with `predicate@lang` keys")
+        }
+
+        /// `predicate@lang` keys holding a string are collected, along with the
+        /// plain string `predicate` keys of the same predicates, which can come
+        /// before or after them. Other values, like lists or `@groupby` blocks,
+        /// are skipped, and facets, like `predicate@lang|facet`, are left to `Facets`.
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<LangStrings, A::Error> {
+            let mut langs = LangStrings::new();
+            let mut untagged = Vec::new();
+            while let Some(key) = access.next_key::<String>()? {
+                if key.contains('|') {
+                    access.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+                match key.find('@') {
+                    Some(at) if at > 0 => {
+                        if let Value::String(text) = access.next_value()? {
+                            langs
+                                .predicates
+                                .entry(key[..at].to_string())
+                                .or_default()
+                                .insert(&key[at + 1..], text);
+                        }
+                    }
+                    _ => {
+                        if let Value::String(text) = access.next_value()? {
+                            untagged.push((key, text));
+                        }
+                    }
+                }
+            }
+
+            for (predicate, text) in untagged {
+                if let Some(values) = langs.predicates.get_mut(&predicate) {
+                    values.insert("", text);
+                }
+            }
+            Ok(langs)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for LangStrings {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<LangStrings, D::Error> {
+            deserializer.deserialize_map(LangStringsVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nquad::NQuadBuilder;
+
+    fn name() -> LangString {
+        LangString::new().with("", "Alice").with("en", "Alice").with("fr", "Alicia")
+    }
+
+    #[test]
+    fn round_trips_nquads() {
+        let nquads = name().to_nquads(NQuadBuilder::blank("alice").predicate("name"));
+        let langs: Vec<_> = nquads.iter().map(|nquad| nquad.lang.as_str()).collect();
+        assert_eq!(langs, vec!["", "en", "fr"]);
+        assert_eq!(LangString::from_nquads(&nquads), name());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_json() {
+        let mut langs = LangStrings::new();
+        langs.insert("name", name());
+
+        let json = serde_json::to_string(&langs).unwrap();
+        assert_eq!(json, r#"{"name":"Alice","name@en":"Alice","name@fr":"Alicia"}"#);
+        assert_eq!(serde_json::from_str::<LangStrings>(&json).unwrap(), langs);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reads_untagged_values_of_tagged_predicates_only() {
+        let json = r#"{"name@fr": "Alicia", "uid": "0x1", "age": 26, "name": "Alice", "name@fr|verified": true}"#;
+        let langs: LangStrings = serde_json::from_str(json).unwrap();
+
+        assert_eq!(langs.len(), 1);
+        assert_eq!(langs.get("name"), Some(&LangString::new().with("", "Alice").with("fr", "Alicia")));
+        assert_eq!(langs.get("uid"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn skips_tagged_keys_without_a_string() {
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, Deserialize)]
+        struct Person {
+            uid: String,
+            #[serde(flatten)]
+            langs: LangStrings,
+        }
+
+        let json = r#"{
+            "uid": "0x1",
+            "name@en": "Alice",
+            "tags@en": ["friendly", "tall"],
+            "name@*": {"en": "Alice"},
+            "nick@fr": null,
+            "@groupby": [{"count": 2}]
+        }"#;
+        let person: Person = serde_json::from_str(json).unwrap();
+
+        assert_eq!(person.uid, "0x1");
+        assert_eq!(person.langs.len(), 1);
+        assert_eq!(person.langs.get("name"), Some(&LangString::new().with("en", "Alice")));
+    }
+}
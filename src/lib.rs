@@ -1,16 +1,50 @@
 #![allow(unused_variables)]
 
+mod assigned;
+mod async_txn;
 mod client;
+#[cfg(feature = "chrono")]
+mod datetime;
+mod errors;
+mod facet;
+mod geo;
+#[cfg(feature = "serde")]
+mod json;
+mod lang;
+mod nquad;
 mod protos;
+pub mod query;
+mod rdf;
+mod retry;
+pub mod schema;
 mod txn;
+mod uid;
+mod value;
 
 use grpcio::{ChannelBuilder, ChannelCredentialsBuilder, EnvBuilder};
 use std::sync::Arc;
 
+pub use assigned::AssignedUids;
+pub use async_txn::AsyncTxn;
 pub use client::Dgraph;
+pub use errors::DgraphError;
+#[cfg(feature = "serde")]
+pub use facet::Facets;
+pub use facet::{FromFacetValue, ToFacetValue};
+pub use geo::{Geometry, MultiPolygon, Point, Polygon};
+#[cfg(feature = "serde")]
+pub use json::QueryResponse;
+#[cfg(feature = "serde")]
+pub use lang::LangStrings;
+pub use lang::LangString;
+pub use nquad::NQuadBuilder;
 pub use protos::api::*;
 pub use protos::api_grpc::*;
+pub use rdf::{format_nquad, format_nquads, parse_nquads, RdfError};
+pub use retry::RetryPolicy;
 pub use txn::Txn;
+pub use uid::Uid;
+pub use value::{FromDgraphValue, ToDgraphValue};
 
 pub fn new_secure_dgraph_client(
     addr: &str,
@@ -0,0 +1,196 @@
+use crate::facet::ToFacetValue;
+use crate::protos::api;
+use crate::uid::Uid;
+
+/// Value used by Dgraph for the `*` wildcard of deletion NQuads.
+pub(crate) const STAR_ALL: &str = "_STAR_ALL";
+
+/// Fluent builder of `api::NQuad` values, for the `set` and `del` fields of
+/// `api::Mutation`.
+#[derive(Clone, Debug, Default)]
+pub struct NQuadBuilder {
+    nquad: api::NQuad,
+}
+
+impl NQuadBuilder {
+    /// Starts a NQuad about the node with the given uid.
+    pub fn new(subject: Uid) -> NQuadBuilder {
+        let mut builder = NQuadBuilder::default();
+        builder.nquad.subject = subject.to_string();
+        builder
+    }
+
+    /// Starts a NQuad about the blank node `_:name`. The `_:` prefix is
+    /// optional. The name is not checked here: one that is not a valid label,
+    /// like a name with whitespace, is refused by `format_nquad` and by the
+    /// server.
+    pub fn blank(name: &str) -> NQuadBuilder {
+        let mut builder = NQuadBuilder::default();
+        builder.nquad.subject = blank_node(name);
+        builder
+    }
+
+    pub fn predicate(mut self, predicate: impl Into<String>) -> Self {
+        self.nquad.predicate = predicate.into();
+        self
+    }
+
+    /// Points the edge to the node with the given uid.
+    pub fn object_uid(mut self, uid: Uid) -> Self {
+        self.nquad.object_id = uid.to_string();
+        self.nquad.clear_object_value();
+        self
+    }
+
+    /// Points the edge to the blank node `_:name`, named as in `blank`.
+    pub fn object_blank(mut self, name: &str) -> Self {
+        self.nquad.object_id = blank_node(name);
+        self.nquad.clear_object_value();
+        self
+    }
+
+    /// Sets a literal object value.
+    pub fn object_value(mut self, value: impl Into<api::Value>) -> Self {
+        self.nquad.object_id.clear();
+        self.nquad.set_object_value(value.into());
+        self
+    }
+
+    /// Sets the language tag of a string value, like `@en`.
+    pub fn lang(mut self, lang: impl Into<String>) -> Self {
+        self.nquad.lang = lang.into();
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.nquad.label = label.into();
+        self
+    }
+
+    pub fn facet(mut self, facet: api::Facet) -> Self {
+        self.nquad.facets.push(facet);
+        self
+    }
+
+    /// Adds the facet `key`, typed after the value, like `(since=2006)`.
+    pub fn facet_value(self, key: impl Into<String>, value: impl ToFacetValue) -> Self {
+        self.facet(api::Facet::with_value(key, value))
+    }
+
+    /// Matches all values of the predicate, `<subject> <predicate> * .`.
+    /// Used in `Mutation.del` to delete them.
+    pub fn all_values(self) -> Self {
+        self.object_value(star())
+    }
+
+    /// Matches all predicates of the node, `<subject> * * .`.
+    /// Used in `Mutation.del` to delete them.
+    pub fn all_predicates(self) -> Self {
+        self.predicate(STAR_ALL).all_values()
+    }
+
+    pub fn build(self) -> api::NQuad {
+        self.nquad
+    }
+}
+
+fn blank_node(name: &str) -> String {
+    if name.starts_with("_:") {
+        name.to_string()
+    } else {
+        format!("_:{}", name)
+    }
+}
+
+fn star() -> api::Value {
+    let mut value = api::Value::new();
+    value.set_default_val(STAR_ALL.to_string());
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::api::Value_oneof_val::*;
+
+    fn object(nquad: &api::NQuad) -> Option<api::Value_oneof_val> {
+        nquad.object_value.as_ref().and_then(|value| value.val.clone())
+    }
+
+    #[test]
+    fn builds_edges_between_uids_and_blank_nodes() {
+        let nquad = NQuadBuilder::new(Uid::new(0x1a)).predicate("friend").object_uid(Uid::new(0x2b)).build();
+        assert_eq!((nquad.subject.as_str(), nquad.object_id.as_str()), ("0x1a", "0x2b"));
+        assert_eq!(object(&nquad), None);
+
+        let nquad = NQuadBuilder::blank("alice").predicate("friend").object_blank("bob").build();
+        assert_eq!((nquad.subject.as_str(), nquad.object_id.as_str()), ("_:alice", "_:bob"));
+    }
+
+    #[test]
+    fn keeps_an_explicit_blank_prefix() {
+        assert_eq!(NQuadBuilder::blank("_:alice").build().subject, "_:alice");
+        assert_eq!(NQuadBuilder::blank("alice").object_blank("_:bob").build().object_id, "_:bob");
+    }
+
+    #[test]
+    fn does_not_check_blank_names() {
+        let nquad = NQuadBuilder::blank("a b").predicate("friend").object_blank("_:_:c").build();
+        assert_eq!((nquad.subject.as_str(), nquad.object_id.as_str()), ("_:a b", "_:_:c"));
+        assert!(crate::rdf::format_nquad(&nquad).is_err());
+    }
+
+    #[test]
+    fn the_last_object_wins() {
+        let nquad = NQuadBuilder::blank("a").object_uid(Uid::new(1)).object_value(26).build();
+        assert!(nquad.object_id.is_empty());
+        assert_eq!(object(&nquad), Some(int_val(26)));
+
+        let nquad = NQuadBuilder::blank("a").object_value(26).object_blank("b").build();
+        assert_eq!(nquad.object_id, "_:b");
+        assert_eq!(object(&nquad), None);
+    }
+
+    #[test]
+    fn sets_lang_label_and_facets() {
+        let nquad = NQuadBuilder::blank("a")
+            .predicate("name")
+            .object_value("Alice")
+            .lang("en")
+            .label("graph")
+            .facet_value("since", 2006)
+            .facet_value("close", true)
+            .build();
+
+        assert_eq!(object(&nquad), Some(str_val("Alice".to_string())));
+        assert_eq!((nquad.lang.as_str(), nquad.label.as_str()), ("en", "graph"));
+        assert_eq!(nquad.facets.len(), 2);
+        assert_eq!(nquad.facets[0].key, "since");
+        assert_eq!(nquad.facets[0].value_as::<i64>().unwrap(), 2006);
+        assert!(nquad.facets[1].value_as::<bool>().unwrap());
+    }
+
+    #[test]
+    fn builds_deletion_wildcards() {
+        let nquad = NQuadBuilder::new(Uid::new(1)).predicate("name").all_values().build();
+        assert_eq!(nquad.predicate, "name");
+        assert_eq!(object(&nquad), Some(default_val(STAR_ALL.to_string())));
+
+        let nquad = NQuadBuilder::new(Uid::new(1)).all_predicates().build();
+        assert_eq!(nquad.predicate, STAR_ALL);
+        assert_eq!(object(&nquad), Some(default_val(STAR_ALL.to_string())));
+        assert!(nquad.object_id.is_empty());
+    }
+
+    #[test]
+    fn builds_empty_fields_by_default() {
+        let nquad = NQuadBuilder::default().build();
+        assert_eq!(nquad, api::NQuad::new());
+
+        let nquad = NQuadBuilder::new(Uid::new(1)).build();
+        assert_eq!(nquad.subject, "0x1");
+        assert!(nquad.predicate.is_empty() && nquad.object_id.is_empty());
+        assert!(nquad.lang.is_empty() && nquad.label.is_empty() && nquad.facets.is_empty());
+        assert_eq!(object(&nquad), None);
+    }
+}
@@ -0,0 +1,1019 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::errors::DgraphError;
+use crate::uid::Uid;
+
+/// Argument of a function or of pagination: a literal or a query variable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arg(String);
+
+impl Arg {
+    /// The query variable `$name`, declared with `Query::var` or `Query::vars`.
+    pub fn var(name: &str) -> Arg {
+        Arg(var_name(name))
+    }
+
+    /// The uids stored in the variable `name` by `as_var`, for `Func::uid`.
+    pub fn uid_var(name: &str) -> Arg {
+        Arg(name.to_string())
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for Arg {
+    fn from(text: &str) -> Self {
+        Arg(quote(text))
+    }
+}
+
+impl From<String> for Arg {
+    fn from(text: String) -> Self {
+        Arg(quote(&text))
+    }
+}
+
+impl From<Uid> for Arg {
+    fn from(uid: Uid) -> Self {
+        Arg(uid.to_string())
+    }
+}
+
+macro_rules! plain_arg {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Arg {
+                fn from(value: $ty) -> Self {
+                    Arg(value.to_string())
+                }
+            }
+        )*
+    };
+}
+
+plain_arg!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool);
+
+/// Function used as the root of a query block or in a filter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Func {
+    name: &'static str,
+    args: Vec<String>,
+}
+
+impl Func {
+    fn new(name: &'static str, args: Vec<String>) -> Func {
+        Func { name, args }
+    }
+
+    pub fn eq(predicate: &str, value: impl Into<Arg>) -> Func {
+        Func::new("eq", vec![predicate.to_string(), value.into().0])
+    }
+
+    pub fn le(predicate: &str, value: impl Into<Arg>) -> Func {
+        Func::new("le", vec![predicate.to_string(), value.into().0])
+    }
+
+    pub fn lt(predicate: &str, value: impl Into<Arg>) -> Func {
+        Func::new("lt", vec![predicate.to_string(), value.into().0])
+    }
+
+    pub fn ge(predicate: &str, value: impl Into<Arg>) -> Func {
+        Func::new("ge", vec![predicate.to_string(), value.into().0])
+    }
+
+    pub fn gt(predicate: &str, value: impl Into<Arg>) -> Func {
+        Func::new("gt", vec![predicate.to_string(), value.into().0])
+    }
+
+    /// Matches the nodes with the given uids, or with the uids held by a
+    /// variable given as `Arg::uid_var` or `Arg::var`.
+    pub fn uid<A: Into<Arg>>(uids: impl IntoIterator<Item = A>) -> Func {
+        Func::new("uid", uids.into_iter().map(|uid| uid.into().0).collect())
+    }
+
+    pub fn has(predicate: &str) -> Func {
+        Func::new("has", vec![predicate.to_string()])
+    }
+
+    pub fn anyofterms(predicate: &str, terms: impl Into<Arg>) -> Func {
+        Func::new("anyofterms", vec![predicate.to_string(), terms.into().0])
+    }
+
+    pub fn allofterms(predicate: &str, terms: impl Into<Arg>) -> Func {
+        Func::new("allofterms", vec![predicate.to_string(), terms.into().0])
+    }
+
+    /// Matches the values against the regular expression, like `/^Ali/i`.
+    pub fn regexp(predicate: &str, pattern: &str, flags: &str) -> Func {
+        let pattern = format!("/{}/{}", pattern.replace('/', "\\/"), flags);
+        Func::new("regexp", vec![predicate.to_string(), pattern])
+    }
+}
+
+impl fmt::Display for Func {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}({})", self.name, self.args.join(", "))
+    }
+}
+
+/// Boolean combination of functions, for the `@filter` directive.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    Func(Func),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn and(self, other: impl Into<Filter>) -> Filter {
+        Filter::And(Box::new(self), Box::new(other.into()))
+    }
+
+    pub fn or(self, other: impl Into<Filter>) -> Filter {
+        Filter::Or(Box::new(self), Box::new(other.into()))
+    }
+
+    pub fn not(filter: impl Into<Filter>) -> Filter {
+        Filter::Not(Box::new(filter.into()))
+    }
+
+    /// Writes the filter, with parentheses around nested `AND` and `OR`.
+    fn write(&self, out: &mut String, nested: bool) {
+        match self {
+            Filter::Func(func) => out.push_str(&func.to_string()),
+            Filter::And(left, right) | Filter::Or(left, right) => {
+                if nested {
+                    out.push('(');
+                }
+                left.write(out, true);
+                out.push_str(if let Filter::And(..) = self { " AND " } else { " OR " });
+                right.write(out, true);
+                if nested {
+                    out.push(')');
+                }
+            }
+            Filter::Not(filter) => {
+                out.push_str("NOT ");
+                filter.write(out, true);
+            }
+        }
+    }
+}
+
+impl From<Func> for Filter {
+    fn from(func: Func) -> Self {
+        Filter::Func(func)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, false);
+        f.write_str(&out)
+    }
+}
+
+/// Arguments, directives and children shared by query blocks and edges.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Selection {
+    var: Option<String>,
+    args: Vec<(&'static str, String)>,
+    filter: Option<Filter>,
+    facets: Option<Vec<String>>,
+    cascade: bool,
+    normalize: bool,
+    children: Vec<Edge>,
+}
+
+impl Selection {
+    fn write_var(&self, out: &mut String) {
+        if let Some(var) = &self.var {
+            out.push_str(var);
+            out.push_str(" as ");
+        }
+    }
+
+    fn write_args(&self, out: &mut String, first: Option<String>) {
+        let args: Vec<_> = first
+            .into_iter()
+            .chain(self.args.iter().map(|(name, value)| format!("{}: {}", name, value)))
+            .collect();
+        if !args.is_empty() {
+            out.push('(');
+            out.push_str(&args.join(", "));
+            out.push(')');
+        }
+    }
+
+    fn write_directives(&self, out: &mut String) {
+        match &self.facets {
+            Some(facets) if facets.is_empty() => out.push_str(" @facets"),
+            Some(facets) => out.push_str(&format!(" @facets({})", facets.join(", "))),
+            None => (),
+        }
+        if let Some(filter) = &self.filter {
+            out.push_str(&format!(" @filter({})", filter));
+        }
+        if self.cascade {
+            out.push_str(" @cascade");
+        }
+        if self.normalize {
+            out.push_str(" @normalize");
+        }
+    }
+
+    /// Writes the selection set, or `{ uid }` if it is empty but `required`.
+    fn write_children(&self, out: &mut String, indent: usize, required: bool) {
+        if self.children.is_empty() && !required {
+            out.push('\n');
+            return;
+        }
+
+        out.push_str(" {\n");
+        if self.children.is_empty() {
+            Edge::new("uid").write(out, indent + 1);
+        }
+        for child in self.children.iter() {
+            child.write(out, indent + 1);
+        }
+        push_indent(out, indent);
+        out.push_str("}\n");
+    }
+}
+
+/// Builder methods shared by `Block` and `Edge`.
+macro_rules! selection_methods {
+    () => {
+        pub fn first(mut self, count: impl Into<Arg>) -> Self {
+            self.selection.args.push(("first", count.into().0));
+            self
+        }
+
+        pub fn offset(mut self, count: impl Into<Arg>) -> Self {
+            self.selection.args.push(("offset", count.into().0));
+            self
+        }
+
+        /// Skips the results up to the given uid, for pagination.
+        pub fn after(mut self, uid: impl Into<Arg>) -> Self {
+            self.selection.args.push(("after", uid.into().0));
+            self
+        }
+
+        pub fn order_asc(mut self, predicate: &str) -> Self {
+            self.selection.args.push(("orderasc", predicate.to_string()));
+            self
+        }
+
+        pub fn order_desc(mut self, predicate: &str) -> Self {
+            self.selection.args.push(("orderdesc", predicate.to_string()));
+            self
+        }
+
+        pub fn filter(mut self, filter: impl Into<Filter>) -> Self {
+            self.selection.filter = Some(filter.into());
+            self
+        }
+
+        /// Requests the given facets of the edges, or all of them if empty.
+        pub fn facets(mut self, facets: &[&str]) -> Self {
+            self.selection.facets = Some(facets.iter().map(|facet| facet.to_string()).collect());
+            self
+        }
+
+        /// Stores the uids of the matched nodes in the variable `name`, for
+        /// use by other blocks with `Func::uid(Arg::uid_var(name))`.
+        pub fn as_var(mut self, name: &str) -> Self {
+            self.selection.var = Some(name.to_string());
+            self
+        }
+
+        /// Only keeps the nodes having all the predicates of the selection.
+        pub fn cascade(mut self) -> Self {
+            self.selection.cascade = true;
+            self
+        }
+
+        /// Flattens the results, only keeping the aliased predicates.
+        pub fn normalize(mut self) -> Self {
+            self.selection.normalize = true;
+            self
+        }
+
+        /// Selects a scalar predicate.
+        pub fn field(self, predicate: &str) -> Self {
+            self.edge(Edge::new(predicate))
+        }
+
+        /// Selects a scalar predicate, named `alias` in the response.
+        pub fn field_as(self, alias: &str, predicate: &str) -> Self {
+            self.edge(Edge::new(predicate).alias(alias))
+        }
+
+        /// Selects a predicate with its own arguments, directives or children.
+        pub fn edge(mut self, edge: Edge) -> Self {
+            self.selection.children.push(edge);
+            self
+        }
+    };
+}
+
+/// Top level block of a query, like `me(func: eq(name, "Alice")) { ... }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    name: String,
+    func: Func,
+    selection: Selection,
+}
+
+impl Block {
+    /// Creates the block `name`, which is the key of its results in the
+    /// response. A block without fields selects the uids of the matched nodes.
+    pub fn new(name: &str, func: Func) -> Block {
+        Block {
+            name: name.to_string(),
+            func,
+            selection: Selection::default(),
+        }
+    }
+
+    selection_methods!();
+
+    fn write(&self, out: &mut String, indent: usize) {
+        push_indent(out, indent);
+        self.selection.write_var(out);
+        out.push_str(&self.name);
+        self.selection.write_args(out, Some(format!("func: {}", self.func)));
+        self.selection.write_directives(out);
+        self.selection.write_children(out, indent, true);
+    }
+}
+
+/// Predicate selected in a block or in another edge, like `friend { name }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    predicate: String,
+    alias: Option<String>,
+    selection: Selection,
+}
+
+impl Edge {
+    pub fn new(predicate: &str) -> Edge {
+        Edge {
+            predicate: predicate.to_string(),
+            alias: None,
+            selection: Selection::default(),
+        }
+    }
+
+    /// Names the predicate `alias` in the response.
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.alias = Some(alias.to_string());
+        self
+    }
+
+    selection_methods!();
+
+    fn write(&self, out: &mut String, indent: usize) {
+        push_indent(out, indent);
+        self.selection.write_var(out);
+        if let Some(alias) = &self.alias {
+            out.push_str(alias);
+            out.push_str(": ");
+        }
+        out.push_str(&self.predicate);
+        self.selection.write_args(out, None);
+        self.selection.write_directives(out);
+        self.selection.write_children(out, indent, false);
+    }
+}
+
+/// GraphQL+- query made of blocks, rendered as text for `Txn::query` and
+/// `Txn::query_with_vars`.
+///
+/// ```
+/// use dgraph::query::{Arg, Block, Edge, Filter, Func, Query};
+///
+/// let query = Query::named("all")
+///     .var("a", "string")
+///     .block(
+///         Block::new("me", Func::eq("name", Arg::var("a")))
+///             .first(10)
+///             .filter(Filter::from(Func::has("age")).and(Func::ge("age", 18)))
+///             .field("name")
+///             .edge(Edge::new("friend").facets(&["since"]).order_asc("name").field("name")),
+///     );
+///
+/// assert_eq!(query.to_string(), r#"query all($a: string) {
+///   me(func: eq(name, $a), first: 10) @filter(has(age) AND ge(age, 18)) {
+///     name
+///     friend(orderasc: name) @facets(since) {
+///       name
+///     }
+///   }
+/// }
+/// "#);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    name: Option<String>,
+    vars: Vec<(String, String)>,
+    blocks: Vec<Block>,
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    pub fn named(name: &str) -> Query {
+        Query {
+            name: Some(name.to_string()),
+            ..Query::default()
+        }
+    }
+
+    /// Declares the variable `$name` with the given type, like `string` or `int`,
+    /// replacing any previous declaration of the same variable.
+    pub fn var(mut self, name: &str, var_type: &str) -> Self {
+        self.declare(var_name(name), var_type.to_string());
+        self
+    }
+
+    /// Declares the variables with their types.
+    pub fn vars(mut self, vars: &Vars) -> Self {
+        for (name, value) in vars.vars.iter() {
+            self.declare(name.clone(), value.var_type().to_string());
+        }
+        self
+    }
+
+    fn declare(&mut self, name: String, var_type: String) {
+        match self.vars.iter_mut().find(|(var, _)| *var == name) {
+            Some(var) => var.1 = var_type,
+            None => self.vars.push((name, var_type)),
+        }
+    }
+
+    pub fn block(mut self, block: Block) -> Self {
+        self.blocks.push(block);
+        self
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+
+        // Variables can only be declared by a named query.
+        if self.name.is_some() || !self.vars.is_empty() {
+            out.push_str("query ");
+            out.push_str(self.name.as_ref().map(String::as_str).unwrap_or("q"));
+            if !self.vars.is_empty() {
+                let vars: Vec<_> = self.vars.iter().map(|(name, ty)| format!("{}: {}", name, ty)).collect();
+                out.push('(');
+                out.push_str(&vars.join(", "));
+                out.push(')');
+            }
+            out.push(' ');
+        }
+
+        out.push_str("{\n");
+        for block in self.blocks.iter() {
+            block.write(&mut out, 1);
+        }
+        out.push_str("}\n");
+
+        f.write_str(&out)
+    }
+}
+
+impl From<&Query> for String {
+    fn from(query: &Query) -> Self {
+        query.to_string()
+    }
+}
+
+impl From<Query> for String {
+    fn from(query: Query) -> Self {
+        query.to_string()
+    }
+}
+
+/// Typed value of a query variable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VarValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// List of uids, for use in `uid($var)`.
+    Uids(Vec<Uid>),
+}
+
+impl VarValue {
+    /// Type of the variable in the query declaration. Uid lists are passed as strings.
+    pub fn var_type(&self) -> &'static str {
+        match self {
+            VarValue::String(_) | VarValue::Uids(_) => "string",
+            VarValue::Int(_) => "int",
+            VarValue::Float(_) => "float",
+            VarValue::Bool(_) => "bool",
+        }
+    }
+}
+
+/// Formats the value the way the server parses variables of its type.
+impl fmt::Display for VarValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VarValue::String(text) => f.write_str(text),
+            VarValue::Int(int) => write!(f, "{}", int),
+            VarValue::Float(float) => write!(f, "{:?}", float),
+            VarValue::Bool(b) => write!(f, "{}", b),
+            VarValue::Uids(uids) => {
+                let uids: Vec<_> = uids.iter().map(Uid::to_string).collect();
+                write!(f, "[{}]", uids.join(", "))
+            }
+        }
+    }
+}
+
+impl From<&str> for VarValue {
+    fn from(text: &str) -> Self {
+        VarValue::String(text.to_string())
+    }
+}
+
+impl From<String> for VarValue {
+    fn from(text: String) -> Self {
+        VarValue::String(text)
+    }
+}
+
+macro_rules! int_var {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for VarValue {
+                fn from(int: $ty) -> Self {
+                    VarValue::Int(i64::from(int))
+                }
+            }
+        )*
+    };
+}
+
+int_var!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<f64> for VarValue {
+    fn from(float: f64) -> Self {
+        VarValue::Float(float)
+    }
+}
+
+impl From<f32> for VarValue {
+    fn from(float: f32) -> Self {
+        VarValue::Float(f64::from(float))
+    }
+}
+
+impl From<bool> for VarValue {
+    fn from(b: bool) -> Self {
+        VarValue::Bool(b)
+    }
+}
+
+impl From<Uid> for VarValue {
+    fn from(uid: Uid) -> Self {
+        VarValue::Uids(vec![uid])
+    }
+}
+
+impl From<Vec<Uid>> for VarValue {
+    fn from(uids: Vec<Uid>) -> Self {
+        VarValue::Uids(uids)
+    }
+}
+
+impl From<&[Uid]> for VarValue {
+    fn from(uids: &[Uid]) -> Self {
+        VarValue::Uids(uids.to_vec())
+    }
+}
+
+/// Typed variables of a query, for `Txn::query_with_typed_vars`.
+///
+/// ```
+/// use dgraph::query::Vars;
+///
+/// let vars = Vars::new().set("name", "Alice").set("$age", 26);
+/// assert_eq!(vars.header("all"), "query all($name: string, $age: int)");
+///
+/// assert!(vars.check("query all($name: string, $age: int) { me(func: eq(name, $name)) { age } }").is_ok());
+/// assert!(vars.check("query all($nick: string) { me(func: eq(name, $nick)) { age } }").is_err());
+/// assert!(vars.check("{ me(func: eq(name, $name)) { age } }").is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vars {
+    vars: Vec<(String, VarValue)>,
+}
+
+impl Vars {
+    pub fn new() -> Vars {
+        Vars::default()
+    }
+
+    /// Sets the variable `$name`, replacing any previous value.
+    pub fn set(mut self, name: &str, value: impl Into<VarValue>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn insert(&mut self, name: &str, value: impl Into<VarValue>) {
+        let name = var_name(name);
+        let value = value.into();
+        match self.vars.iter_mut().find(|(var, _)| *var == name) {
+            Some(var) => var.1 = value,
+            None => self.vars.push((name, value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VarValue> {
+        let name = var_name(name);
+        self.vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value)
+    }
+
+    /// Declaration of the variables, like `query all($a: string, $b: int)`, or
+    /// `query all` if there is none, as the server rejects empty parentheses.
+    pub fn header(&self, query_name: &str) -> String {
+        if self.vars.is_empty() {
+            return format!("query {}", query_name);
+        }
+
+        let vars: Vec<_> = self
+            .vars
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value.var_type()))
+            .collect();
+        format!("query {}({})", query_name, vars.join(", "))
+    }
+
+    /// Checks that every variable used by the query is declared by its
+    /// `query name(...)` header, and is set unless declared with a default value.
+    pub fn check(&self, query: &str) -> Result<(), DgraphError> {
+        let ScannedVars { used, declared, defaults } = scan_vars(query);
+        if let Some(name) = used.iter().find(|name| !declared.contains(*name)) {
+            return Err(DgraphError::UndeclaredVar(name.clone()));
+        }
+
+        match used
+            .into_iter()
+            .find(|name| self.get(name).is_none() && !defaults.contains(name))
+        {
+            Some(name) => Err(DgraphError::UnsetVar(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// The variables in the form expected by `Txn::query_with_vars`.
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.vars
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect()
+    }
+}
+
+/// Variables found in a query by `scan_vars`.
+#[derive(Debug, Default, PartialEq)]
+struct ScannedVars {
+    /// Variables used by the blocks, in order of appearance.
+    used: Vec<String>,
+    /// Variables declared by the `query name(...)` header.
+    declared: HashSet<String>,
+    /// Declared variables with a default value.
+    defaults: HashSet<String>,
+}
+
+/// Finds the variables declared by the header of the query, which is the text
+/// before the first `{`, and the ones used by its blocks. String literals,
+/// regular expressions and comments are skipped.
+fn scan_vars(query: &str) -> ScannedVars {
+    let chars: Vec<char> = query.chars().collect();
+    let mut vars = ScannedVars::default();
+    let mut in_header = true;
+    let mut pos = 0;
+
+    let skip_while = |mut pos: usize, pred: &dyn Fn(char) -> bool| {
+        while pos < chars.len() && pred(chars[pos]) {
+            pos += 1;
+        }
+        pos
+    };
+    let skip_quoted = |mut pos: usize| {
+        let delimiter = chars[pos];
+        pos += 1;
+        while pos < chars.len() && chars[pos] != delimiter {
+            pos += if chars[pos] == '\\' { 2 } else { 1 };
+        }
+        pos + 1
+    };
+    let is_name = |c: char| c.is_alphanumeric() || c == '_';
+
+    while pos < chars.len() {
+        match chars[pos] {
+            '"' => pos = skip_quoted(pos),
+            '/' if opens_regexp(&chars[..pos]) => pos = skip_quoted(pos),
+            '#' => pos = skip_while(pos, &|c| c != '\n'),
+            '{' => {
+                in_header = false;
+                pos += 1;
+            }
+            '$' => {
+                let end = skip_while(pos + 1, &is_name);
+                let name: String = chars[pos..end].iter().collect();
+                pos = end;
+
+                if !in_header {
+                    vars.used.push(name);
+                    continue;
+                }
+
+                // A declaration, `$name: type`, possibly followed by `= default`.
+                pos = skip_while(pos, &char::is_whitespace);
+                if chars.get(pos) == Some(&':') {
+                    pos = skip_while(pos + 1, &char::is_whitespace);
+                    pos = skip_while(pos, &|c| is_name(c) || c == '!' || c == '[' || c == ']');
+                    pos = skip_while(pos, &char::is_whitespace);
+                    if chars.get(pos) == Some(&'=') {
+                        vars.defaults.insert(name.clone());
+                    }
+                }
+                vars.declared.insert(name);
+            }
+            _ => pos += 1,
+        }
+    }
+
+    vars
+}
+
+/// Tells if a `/` following the text opens the pattern of `regexp(predicate, /.../)`,
+/// rather than being a division, like in `math(a / 2)`.
+fn opens_regexp(before: &[char]) -> bool {
+    let before: String = before.iter().collect();
+    let args = match before.trim_end().strip_suffix(',') {
+        Some(args) => args,
+        None => return false,
+    };
+    let open = match args.rfind('(') {
+        Some(open) => open,
+        None => return false,
+    };
+    if args[open + 1..].contains(|c| c == ',' || c == ')') {
+        return false;
+    }
+
+    let func = args[..open].trim_end();
+    func.ends_with("regexp") && !func[..func.len() - "regexp".len()].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+/// Adds the `$` prefix of query variables, if missing.
+pub(crate) fn var_name(name: &str) -> String {
+    if name.starts_with('$') {
+        name.to_string()
+    } else {
+        format!("${}", name)
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_unnamed_queries() {
+        let query = Query::new().block(Block::new("me", Func::uid(vec![Uid::new(1)])).field("name"));
+        assert_eq!(query.to_string(), "{\n  me(func: uid(0x1)) {\n    name\n  }\n}\n");
+    }
+
+    #[test]
+    fn renders_functions() {
+        assert_eq!(Func::eq("name", "Alice").to_string(), r#"eq(name, "Alice")"#);
+        assert_eq!(Func::le("age", 30).to_string(), "le(age, 30)");
+        assert_eq!(Func::lt("age", 30).to_string(), "lt(age, 30)");
+        assert_eq!(Func::ge("height", 1.5).to_string(), "ge(height, 1.5)");
+        assert_eq!(Func::gt("age", Arg::var("age")).to_string(), "gt(age, $age)");
+        assert_eq!(Func::has("friend").to_string(), "has(friend)");
+        assert_eq!(Func::anyofterms("name", "Alice Bob").to_string(), r#"anyofterms(name, "Alice Bob")"#);
+        assert_eq!(Func::allofterms("name", "Alice Bob").to_string(), r#"allofterms(name, "Alice Bob")"#);
+        assert_eq!(Func::regexp("name", "^Al/i", "i").to_string(), r"regexp(name, /^Al\/i/i)");
+        assert_eq!(Func::uid(vec![Uid::new(1), Uid::new(0x2a)]).to_string(), "uid(0x1, 0x2a)");
+        assert_eq!(Func::uid(vec![Arg::uid_var("friends")]).to_string(), "uid(friends)");
+    }
+
+    #[test]
+    fn escapes_strings() {
+        let text = "say \"hi\"\\\n\r\t\u{7}";
+        assert_eq!(Func::eq("name", text).to_string(), r#"eq(name, "say \"hi\"\\\n\r\t\u0007")"#);
+    }
+
+    #[test]
+    fn renders_nested_filters() {
+        let filter = Filter::from(Func::has("age"))
+            .and(Filter::from(Func::eq("name", "Alice")).or(Func::eq("name", "Bob")))
+            .and(Filter::not(Func::has("deleted")));
+        assert_eq!(
+            filter.to_string(),
+            r#"(has(age) AND (eq(name, "Alice") OR eq(name, "Bob"))) AND NOT has(deleted)"#
+        );
+    }
+
+    #[test]
+    fn renders_pagination_order_and_directives() {
+        let query = Query::new().block(
+            Block::new("people", Func::has("name"))
+                .first(10)
+                .offset(20)
+                .after(Uid::new(0x10))
+                .order_desc("age")
+                .facets(&[])
+                .cascade()
+                .normalize()
+                .field_as("n", "name")
+                .edge(Edge::new("friend").first(-1).order_asc("name").filter(Func::has("email")).field("name")),
+        );
+        assert_eq!(
+            query.to_string(),
+            "{
+  people(func: has(name), first: 10, offset: 20, after: 0x10, orderdesc: age) @facets @cascade @normalize {
+    n: name
+    friend(first: -1, orderasc: name) @filter(has(email)) {
+      name
+    }
+  }
+}
+"
+        );
+    }
+
+    #[test]
+    fn renders_uid_variables() {
+        let query = Query::new()
+            .block(Block::new("var", Func::eq("name", "Alice")).edge(Edge::new("friend").as_var("friends")))
+            .block(Block::new("friends", Func::uid(vec![Arg::uid_var("friends")])).field("name"))
+            .block(Block::new("alice", Func::eq("name", "Alice")).as_var("a"));
+        assert_eq!(
+            query.to_string(),
+            r#"{
+  var(func: eq(name, "Alice")) {
+    friends as friend
+  }
+  friends(func: uid(friends)) {
+    name
+  }
+  a as alice(func: eq(name, "Alice")) {
+    uid
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn renders_blocks_without_fields_with_uids() {
+        let query = Query::new().block(Block::new("me", Func::has("name")));
+        assert_eq!(query.to_string(), "{\n  me(func: has(name)) {\n    uid\n  }\n}\n");
+    }
+
+    /// Returns the used, declared and defaulted variables, the last two sorted.
+    fn scan(query: &str) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let scanned = scan_vars(query);
+        let sorted = |names: HashSet<String>| {
+            let mut names: Vec<_> = names.into_iter().collect();
+            names.sort();
+            names
+        };
+        (scanned.used, sorted(scanned.declared), sorted(scanned.defaults))
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn scans_declared_and_used_variables() {
+        let query = r#"query q($a: string, $b: int = 10, $c: [uid]!) {
+            me(func: eq(name, $a), first: $b) @filter(uid($c)) { name }
+        }"#;
+        assert_eq!(scan(query), (names(&["$a", "$b", "$c"]), names(&["$a", "$b", "$c"]), names(&["$b"])));
+        assert_eq!(scan("{ me(func: eq(name, $a)) { name } }"), (names(&["$a"]), names(&[]), names(&[])));
+    }
+
+    #[test]
+    fn scans_past_strings_regexps_and_comments() {
+        let query = r#"query q($a: string = "$x {") {
+            # $comment
+            me(func: regexp(name, /\$re\/{/i)) @filter(eq(nick, "\"$quoted")) { name }
+            other(func: eq(name, $a)) { name }
+        }"#;
+        assert_eq!(scan(query), (names(&["$a"]), names(&["$a"]), names(&["$a"])));
+    }
+
+    #[test]
+    fn scans_divisions_as_divisions() {
+        let query = "query q($x: int) { var(func: has(b)) { d as math(b / 2) } me(func: eq(n, $y)) { val(d) } }";
+        assert_eq!(scan(query), (names(&["$y"]), names(&["$x"]), names(&[])));
+        assert!(opens_regexp(&"regexp( name ,".chars().collect::<Vec<_>>()));
+        assert!(opens_regexp(&"@filter(regexp(<name@en>, ".chars().collect::<Vec<_>>()));
+        assert!(!opens_regexp(&"myregexp(name,".chars().collect::<Vec<_>>()));
+        assert!(!opens_regexp(&"math(b ".chars().collect::<Vec<_>>()));
+        assert!(!opens_regexp(&"eq(name,".chars().collect::<Vec<_>>()));
+    }
+
+    #[test]
+    fn checks_declarations_and_values() {
+        let vars = Vars::new().set("a", "Alice");
+
+        assert!(vars.check("query q($a: string) { me(func: eq(name, $a)) { name } }").is_ok());
+        assert!(Vars::new().check("query q($a: string = \"Bob\") { me(func: eq(name, $a)) { name } }").is_ok());
+        assert!(vars.check("{ me(func: has(name)) { name } }").is_ok());
+
+        match vars.check("{ me(func: eq(name, $a)) { name } }") {
+            Err(DgraphError::UndeclaredVar(name)) => assert_eq!(name, "$a"),
+            res => panic!("unexpected {:?}", res),
+        }
+        match Vars::new().check("query q($x: int) { me(func: has(name), first: $x) { name } }") {
+            Err(DgraphError::UnsetVar(name)) => assert_eq!(name, "$x"),
+            res => panic!("unexpected {:?}", res),
+        }
+        match Vars::new().check("query q($x: int) { var(func: has(b)) { d as math(b / 2) } me(func: eq(n, $y)) { val(d) } }") {
+            Err(DgraphError::UndeclaredVar(name)) => assert_eq!(name, "$y"),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[test]
+    fn declares_variables() {
+        let vars = Vars::new().set("name", "Alice").set("age", 26);
+        let query = Query::new()
+            .vars(&vars)
+            .block(Block::new("me", Func::eq("name", Arg::var("name"))).filter(Func::ge("age", Arg::var("age"))).field("uid"));
+        assert_eq!(
+            query.to_string(),
+            "query q($name: string, $age: int) {\n  me(func: eq(name, $name)) @filter(ge(age, $age)) {\n    uid\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn declares_each_variable_once() {
+        let vars = Vars::new().set("name", "Alice").set("age", 26);
+        let query = Query::named("all")
+            .var("$age", "string")
+            .vars(&vars)
+            .vars(&vars)
+            .var("name", "string")
+            .block(Block::new("me", Func::eq("name", Arg::var("name"))).field("uid"));
+        assert!(query.to_string().starts_with("query all($age: int, $name: string) {\n"));
+    }
+
+    #[test]
+    fn writes_headers_without_empty_parentheses() {
+        assert_eq!(Vars::new().header("all"), "query all");
+        assert_eq!(Vars::new().set("a", true).header("all"), "query all($a: bool)");
+
+        let query = format!("{} {{ me(func: has(name)) {{ name }} }}", Vars::new().header("all"));
+        assert!(Vars::new().check(&query).is_ok());
+    }
+}
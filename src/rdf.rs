@@ -0,0 +1,872 @@
+use failure::Fail;
+
+#[cfg(feature = "chrono")]
+use crate::datetime;
+use crate::errors::DgraphError;
+#[cfg(feature = "serde")]
+use crate::geo::Geometry;
+use crate::nquad::STAR_ALL;
+use crate::protos::api;
+
+const XML_SCHEMA: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Errors returned when parsing or writing RDF N-Quads.
+#[derive(Debug, Fail, PartialEq)]
+pub enum RdfError {
+    #[fail(display = "Invalid RDF at line {}, column {}: {}", line, column, message)]
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[fail(display = "Cannot write {} as RDF", _0)]
+    Unsupported(String),
+}
+
+/// Parses RDF N-Quad text, as accepted by `Mutation.set_nquads` and
+/// `Mutation.del_nquads`, into NQuads for the `set` and `del` fields.
+///
+/// Besides plain N-Quads, the Dgraph extensions are supported: `<0x1a>` uids,
+/// `*` wildcards, language tags, `^^<xs:type>` literals and facets in parentheses.
+///
+/// ```
+/// let text = r#"_:alice <name> "Alice \"Al\""@en (since=2006, close=true) .
+/// _:alice <age> "26"^^<xs:int> .
+/// _:alice <friend> <0x1a> .
+/// "#;
+///
+/// let nquads = dgraph::parse_nquads(text).unwrap();
+/// assert_eq!(nquads.len(), 3);
+/// assert_eq!(nquads[0].lang, "en");
+/// assert_eq!(dgraph::format_nquads(&nquads).unwrap(), text);
+/// ```
+pub fn parse_nquads(text: &str) -> Result<Vec<api::NQuad>, RdfError> {
+    let mut nquads = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let mut parser = LineParser {
+            chars: line.chars().collect(),
+            pos: 0,
+            line: index + 1,
+        };
+
+        if let Some(nquad) = parser.parse()? {
+            nquads.push(nquad);
+        }
+    }
+
+    Ok(nquads)
+}
+
+/// Writes the NQuads as RDF text, one per line, escaping IRIs and literals.
+///
+/// ```
+/// use dgraph::{NQuadBuilder, Uid};
+///
+/// let mut name = dgraph::Value::new();
+/// name.set_default_val("Bob \"the builder\"\n".to_string());
+///
+/// let nquads = vec![
+///     NQuadBuilder::new(Uid::new(0x1a)).predicate("name").object_value(name).build(),
+///     NQuadBuilder::new(Uid::new(0x1a)).all_predicates().build(),
+/// ];
+///
+/// let text = dgraph::format_nquads(&nquads).unwrap();
+/// assert_eq!(text, "<0x1a> <name> \"Bob \\\"the builder\\\"\\n\" .\n<0x1a> * * .\n");
+/// assert_eq!(dgraph::parse_nquads(&text).unwrap(), nquads);
+/// ```
+pub fn format_nquads(nquads: &[api::NQuad]) -> Result<String, RdfError> {
+    let mut out = String::new();
+    for nquad in nquads {
+        out.push_str(&format_nquad(nquad)?);
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+/// Writes a single NQuad as a line of RDF text, without the trailing newline.
+///
+/// Blank nodes with a label that `parse_nquads` would not read back, like one
+/// with whitespace, are refused. `uid_val` objects are written as `<0x1a>`,
+/// which reads back as `object_id`; `NQuad::object_uid` returns the uid in
+/// both cases.
+pub fn format_nquad(nquad: &api::NQuad) -> Result<String, RdfError> {
+    let mut out = String::new();
+
+    write_node(&mut out, &nquad.subject)?;
+    out.push(' ');
+
+    if nquad.predicate == STAR_ALL {
+        out.push('*');
+    } else {
+        write_iri(&mut out, &nquad.predicate);
+    }
+    out.push(' ');
+
+    if !nquad.object_id.is_empty() {
+        write_node(&mut out, &nquad.object_id)?;
+    } else {
+        match nquad.object_value.as_ref() {
+            Some(value) => write_value(&mut out, value, &nquad.lang)?,
+            None => return Err(RdfError::Unsupported("a NQuad without object".to_string())),
+        }
+    }
+
+    if !nquad.label.is_empty() {
+        out.push(' ');
+        write_node(&mut out, &nquad.label)?;
+    }
+
+    if !nquad.facets.is_empty() {
+        out.push_str(" (");
+        for (i, facet) in nquad.facets.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            out.push_str(&facet.key);
+            out.push('=');
+            write_facet_value(&mut out, facet)?;
+        }
+        out.push(')');
+    }
+
+    out.push_str(" .");
+    Ok(out)
+}
+
+struct LineParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl LineParser {
+    fn parse(&mut self) -> Result<Option<api::NQuad>, RdfError> {
+        self.skip_whitespace();
+        if self.at_line_end() {
+            return Ok(None);
+        }
+
+        let mut nquad = api::NQuad::new();
+        nquad.subject = self.node("subject")?;
+        self.skip_whitespace();
+
+        nquad.predicate = if self.eat('*') {
+            STAR_ALL.to_string()
+        } else {
+            self.iri()?
+        };
+        self.skip_whitespace();
+
+        self.object(&mut nquad)?;
+        self.skip_whitespace();
+
+        if let Some('<') | Some('_') = self.peek() {
+            nquad.label = self.node("label")?;
+            self.skip_whitespace();
+        }
+
+        if self.peek() == Some('(') {
+            nquad.facets = self.facets()?.into();
+            self.skip_whitespace();
+        }
+
+        self.expect('.')?;
+        self.skip_whitespace();
+        if !self.at_line_end() {
+            return self.error("unexpected characters after the end of the NQuad");
+        }
+
+        Ok(Some(nquad))
+    }
+
+    fn node(&mut self, what: &str) -> Result<String, RdfError> {
+        match self.peek() {
+            Some('<') => self.iri(),
+            Some('_') => self.blank_node(),
+            _ => self.error(format!("expected {} as an IRI or a blank node", what)),
+        }
+    }
+
+    fn iri(&mut self) -> Result<String, RdfError> {
+        self.expect('<')?;
+        let mut iri = String::new();
+        loop {
+            match self.next() {
+                Some('>') => break,
+                Some('\\') => match self.next() {
+                    Some('u') => iri.push(self.hex_char(4)?),
+                    Some('U') => iri.push(self.hex_char(8)?),
+                    _ => return self.error("invalid escape sequence in IRI"),
+                },
+                Some(c) if c <= ' ' || c == '<' || c == '"' => {
+                    self.pos -= 1;
+                    return self.error(format!("invalid character {:?} in IRI", c));
+                }
+                Some(c) => iri.push(c),
+                None => return self.error("unterminated IRI"),
+            }
+        }
+
+        if iri.is_empty() {
+            return self.error("empty IRI");
+        }
+
+        Ok(iri)
+    }
+
+    fn blank_node(&mut self) -> Result<String, RdfError> {
+        self.expect('_')?;
+        self.expect(':')?;
+
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if is_blank_label_char(c) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        // A blank node label cannot end with a dot, which terminates the NQuad.
+        while self.pos > start && self.chars[self.pos - 1] == '.' {
+            self.pos -= 1;
+        }
+
+        if self.pos == start {
+            return self.error("empty blank node label");
+        }
+
+        let label: String = self.chars[start..self.pos].iter().collect();
+        Ok(format!("_:{}", label))
+    }
+
+    fn object(&mut self, nquad: &mut api::NQuad) -> Result<(), RdfError> {
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                let mut value = api::Value::new();
+                value.set_default_val(STAR_ALL.to_string());
+                nquad.set_object_value(value);
+            }
+            Some('"') => self.literal(nquad)?,
+            Some('<') | Some('_') => nquad.object_id = self.node("object")?,
+            _ => return self.error("expected object as an IRI, a blank node, a literal or *"),
+        }
+
+        Ok(())
+    }
+
+    fn literal(&mut self, nquad: &mut api::NQuad) -> Result<(), RdfError> {
+        let text = self.string()?;
+
+        let value = if self.eat('@') {
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            if self.pos == start {
+                return self.error("empty language tag");
+            }
+            nquad.lang = self.chars[start..self.pos].iter().collect();
+
+            let mut value = api::Value::new();
+            value.set_default_val(text);
+            value
+        } else if self.eat('^') {
+            self.expect('^')?;
+            let start = self.pos;
+            let ty = self.iri()?;
+            match typed_value(text, &ty) {
+                Ok(value) => value,
+                Err(message) => {
+                    self.pos = start;
+                    return self.error(message);
+                }
+            }
+        } else {
+            let mut value = api::Value::new();
+            value.set_default_val(text);
+            value
+        };
+
+        nquad.set_object_value(value);
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String, RdfError> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                Some('"') => break,
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('f') => '\u{c}',
+                        Some('"') => '"',
+                        Some('\'') => '\'',
+                        Some('\\') => '\\',
+                        Some('u') => self.hex_char(4)?,
+                        Some('U') => self.hex_char(8)?,
+                        _ => return self.error("invalid escape sequence in string"),
+                    };
+                    text.push(c);
+                }
+                Some(c) => text.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+
+        Ok(text)
+    }
+
+    fn hex_char(&mut self, digits: usize) -> Result<char, RdfError> {
+        let start = self.pos;
+        let end = start + digits;
+        if end > self.chars.len() {
+            return self.error("truncated unicode escape");
+        }
+
+        let hex: String = self.chars[start..end].iter().collect();
+        match u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32) {
+            Some(c) => {
+                self.pos = end;
+                Ok(c)
+            }
+            None => self.error(format!("invalid unicode escape {:?}", hex)),
+        }
+    }
+
+    fn facets(&mut self) -> Result<Vec<api::Facet>, RdfError> {
+        self.expect('(')?;
+        let mut facets = Vec::new();
+
+        self.skip_whitespace();
+        if self.eat(')') {
+            return Ok(facets);
+        }
+
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            if self.pos == start {
+                return self.error("expected facet key");
+            }
+            let key: String = self.chars[start..self.pos].iter().collect();
+
+            self.skip_whitespace();
+            self.expect('=')?;
+            self.skip_whitespace();
+
+            let facet = if self.peek() == Some('"') {
+                api::Facet::with_value(key, self.string()?)
+            } else {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    if c == ',' || c == ')' || c == ' ' || c == '\t' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                let token: String = self.chars[start..self.pos].iter().collect();
+                match unquoted_facet(key, &token) {
+                    Ok(facet) => facet,
+                    Err(message) => {
+                        self.pos = start;
+                        return self.error(message);
+                    }
+                }
+            };
+            facets.push(facet);
+
+            self.skip_whitespace();
+            if self.eat(',') {
+                continue;
+            }
+            if self.eat(')') {
+                break;
+            }
+            return self.error("expected ',' or ')' after facet");
+        }
+
+        Ok(facets)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, RdfError> {
+        Err(RdfError::Parse {
+            line: self.line,
+            column: self.pos + 1,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), RdfError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(format!("expected {:?}", c))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn at_line_end(&self) -> bool {
+        match self.peek() {
+            None | Some('#') => true,
+            _ => false,
+        }
+    }
+}
+
+fn typed_value(text: String, ty: &str) -> Result<api::Value, String> {
+    let ty = if ty.starts_with(XML_SCHEMA) {
+        format!("xs:{}", &ty[XML_SCHEMA.len()..])
+    } else {
+        ty.to_string()
+    };
+
+    let mut value = api::Value::new();
+    match ty.as_str() {
+        "xs:string" => value.set_str_val(text),
+        "xs:int" | "xs:integer" | "xs:positiveInteger" => match text.parse() {
+            Ok(int) => value.set_int_val(int),
+            Err(_) => return Err(format!("invalid integer {:?}", text)),
+        },
+        "xs:float" | "xs:double" => match text.parse() {
+            Ok(float) => value.set_double_val(float),
+            Err(_) => return Err(format!("invalid float {:?}", text)),
+        },
+        "xs:boolean" => match parse_bool(&text) {
+            Some(b) => value.set_bool_val(b),
+            None => return Err(format!("invalid boolean {:?}", text)),
+        },
+        "xs:password" => value.set_password_val(text),
+        #[cfg(feature = "chrono")]
+        "xs:dateTime" | "xs:date" => match datetime::parse_datetime(&text) {
+            Some(dt) if ty == "xs:date" => value.set_date_val(datetime::encode_datetime(&dt)),
+            Some(dt) => value.set_datetime_val(datetime::encode_datetime(&dt)),
+            None => return Err(format!("invalid datetime {:?}", text)),
+        },
+        #[cfg(feature = "serde")]
+        "geo:geojson" => match serde_json::from_str::<Geometry>(&text) {
+            Ok(geometry) => value.set_geo_val(geometry.to_wkb()),
+            Err(err) => return Err(format!("invalid GeoJSON: {}", err)),
+        },
+        _ => return Err(format!("unsupported literal type <{}>", ty)),
+    }
+
+    Ok(value)
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text {
+        "1" | "t" | "T" | "true" | "TRUE" | "True" => Some(true),
+        "0" | "f" | "F" | "false" | "FALSE" | "False" => Some(false),
+        _ => None,
+    }
+}
+
+fn unquoted_facet(key: String, token: &str) -> Result<api::Facet, String> {
+    let numeric = token.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.');
+    if let (true, Ok(int)) = (numeric, token.parse::<i64>()) {
+        Ok(api::Facet::with_value(key, int))
+    } else if let (true, Ok(float)) = (numeric, token.parse::<f64>()) {
+        Ok(api::Facet::with_value(key, float))
+    } else if token == "true" || token == "false" {
+        Ok(api::Facet::with_value(key, token == "true"))
+    } else {
+        datetime_facet(key, token).ok_or_else(|| format!("could not parse the facet value {:?}", token))
+    }
+}
+
+#[cfg(feature = "chrono")]
+fn datetime_facet(key: String, token: &str) -> Option<api::Facet> {
+    datetime::parse_datetime(token).map(|dt| api::Facet::with_value(key, dt))
+}
+
+#[cfg(not(feature = "chrono"))]
+fn datetime_facet(_key: String, _token: &str) -> Option<api::Facet> {
+    None
+}
+
+fn write_facet_value(out: &mut String, facet: &api::Facet) -> Result<(), RdfError> {
+    let malformed = |err: DgraphError| RdfError::Unsupported(err.to_string());
+
+    match facet.val_type {
+        api::Facet_ValType::STRING => write_string(out, &facet.value_as::<String>().map_err(malformed)?),
+        api::Facet_ValType::INT => out.push_str(&facet.value_as::<i64>().map_err(malformed)?.to_string()),
+        api::Facet_ValType::FLOAT => out.push_str(&format!("{:?}", facet.value_as::<f64>().map_err(malformed)?)),
+        api::Facet_ValType::BOOL => out.push_str(&facet.value_as::<bool>().map_err(malformed)?.to_string()),
+        #[cfg(feature = "chrono")]
+        api::Facet_ValType::DATETIME => {
+            let dt = facet.value_as().map_err(malformed)?;
+            out.push_str(&datetime::format_datetime(&dt));
+        }
+        #[cfg(not(feature = "chrono"))]
+        api::Facet_ValType::DATETIME => {
+            return Err(RdfError::Unsupported(format!("datetime facet {:?}", facet.key)));
+        }
+    }
+
+    Ok(())
+}
+
+fn write_value(out: &mut String, value: &api::Value, lang: &str) -> Result<(), RdfError> {
+    use crate::protos::api::Value_oneof_val::*;
+
+    match value.val.as_ref() {
+        Some(default_val(text)) if text == STAR_ALL => out.push('*'),
+        Some(default_val(text)) => {
+            write_string(out, text);
+            write_lang(out, lang);
+        }
+        Some(str_val(text)) if !lang.is_empty() => {
+            write_string(out, text);
+            write_lang(out, lang);
+        }
+        Some(str_val(text)) => write_typed(out, text, "xs:string"),
+        Some(int_val(int)) => write_typed(out, &int.to_string(), "xs:int"),
+        Some(double_val(float)) => write_typed(out, &format!("{:?}", float), "xs:float"),
+        Some(bool_val(b)) => write_typed(out, &b.to_string(), "xs:boolean"),
+        Some(password_val(password)) => write_typed(out, password, "xs:password"),
+        Some(uid_val(uid)) => write_iri(out, &format!("{:#x}", uid)),
+        Some(bytes_val(_)) => return Err(RdfError::Unsupported("a bytes value".to_string())),
+        #[cfg(feature = "serde")]
+        Some(geo_val(bytes)) => match Geometry::from_wkb(bytes) {
+            Ok(geometry) => write_typed(out, &serde_json::to_string(&geometry).unwrap(), "geo:geojson"),
+            Err(_) => return Err(RdfError::Unsupported("a malformed geo value".to_string())),
+        },
+        #[cfg(not(feature = "serde"))]
+        Some(geo_val(_)) => return Err(RdfError::Unsupported("a geo value".to_string())),
+        #[cfg(feature = "chrono")]
+        Some(date_val(bytes)) => match datetime::decode_datetime(bytes) {
+            Ok(dt) => write_typed(out, &dt.format("%Y-%m-%d").to_string(), "xs:date"),
+            Err(_) => return Err(RdfError::Unsupported("a malformed date value".to_string())),
+        },
+        #[cfg(feature = "chrono")]
+        Some(datetime_val(bytes)) => match datetime::decode_datetime(bytes) {
+            Ok(dt) => write_typed(out, &datetime::format_datetime(&dt), "xs:dateTime"),
+            Err(_) => return Err(RdfError::Unsupported("a malformed datetime value".to_string())),
+        },
+        #[cfg(not(feature = "chrono"))]
+        Some(date_val(_)) => return Err(RdfError::Unsupported("a date value".to_string())),
+        #[cfg(not(feature = "chrono"))]
+        Some(datetime_val(_)) => return Err(RdfError::Unsupported("a datetime value".to_string())),
+        None => return Err(RdfError::Unsupported("an empty value".to_string())),
+    }
+
+    Ok(())
+}
+
+fn write_lang(out: &mut String, lang: &str) {
+    if !lang.is_empty() {
+        out.push('@');
+        out.push_str(lang);
+    }
+}
+
+fn write_typed(out: &mut String, text: &str, ty: &str) {
+    write_string(out, text);
+    out.push_str("^^<");
+    out.push_str(ty);
+    out.push('>');
+}
+
+fn write_node(out: &mut String, node: &str) -> Result<(), RdfError> {
+    if node.starts_with("_:") {
+        let label = &node[2..];
+        if label.is_empty() || label.ends_with('.') || !label.chars().all(is_blank_label_char) {
+            return Err(RdfError::Unsupported(format!("the blank node {:?}", node)));
+        }
+        out.push_str(node);
+    } else {
+        write_iri(out, node);
+    }
+
+    Ok(())
+}
+
+fn is_blank_label_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+fn write_iri(out: &mut String, iri: &str) {
+    out.push('<');
+    for c in iri.chars() {
+        match c {
+            '\u{0}'..=' ' | '<' | '>' | '"' | '{' | '}' | '|' | '^' | '`' | '\\' => {
+                out.push_str(&format!("\\u{:04X}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('>');
+}
+
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nquad::NQuadBuilder;
+    use crate::protos::api::Value_oneof_val::*;
+    use crate::uid::Uid;
+
+    /// Checks that the formatted NQuads parse back to the same NQuads and
+    /// returns the formatted text.
+    fn round_trip(text: &str) -> String {
+        let nquads = parse_nquads(text).unwrap();
+        let formatted = format_nquads(&nquads).unwrap();
+        assert_eq!(parse_nquads(&formatted).unwrap(), nquads, "{}", formatted);
+        formatted
+    }
+
+    fn assert_canonical(text: &str) {
+        assert_eq!(round_trip(text), text);
+    }
+
+    fn object(text: &str) -> api::Value_oneof_val {
+        let nquads = parse_nquads(text).unwrap();
+        nquads[0].object_value.as_ref().unwrap().val.clone().unwrap()
+    }
+
+    fn parse_error(text: &str) -> (usize, usize, String) {
+        match parse_nquads(text) {
+            Err(RdfError::Parse { line, column, message }) => (line, column, message),
+            res => panic!("expected a parse error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn round_trips_escapes() {
+        assert_canonical("_:a <name> \"quote \\\" tab \\t backslash \\\\ newline \\n return \\r\" .\n");
+        assert_canonical("_:a <name> \"bell \\u0007\" .\n");
+
+        assert_eq!(round_trip("_:a <name> \"\\u00e9\\U0001F600\" .\n"), "_:a <name> \"\u{e9}\u{1F600}\" .\n");
+        assert_eq!(object("_:a <name> \"\\u00e9\\U0001F600\" ."), default_val("\u{e9}\u{1F600}".to_string()));
+        assert_eq!(round_trip("<a\\u0020b> <p> <c> .\n"), "<a\\u0020b> <p> <c> .\n");
+    }
+
+    #[test]
+    fn round_trips_language_tags() {
+        assert_canonical("_:a <name> \"colour\"@en .\n");
+        assert_canonical("_:a <name> \"color\"@en-US .\n");
+
+        let nquads = parse_nquads("_:a <name> \"color\"@en-US .").unwrap();
+        assert_eq!(nquads[0].lang, "en-US");
+    }
+
+    #[test]
+    fn round_trips_typed_literals() {
+        assert_canonical("_:a <age> \"26\"^^<xs:int> .\n");
+        assert_canonical("_:a <age> \"-26\"^^<xs:int> .\n");
+        assert_canonical("_:a <weight> \"1.5\"^^<xs:float> .\n");
+        assert_canonical("_:a <alive> \"true\"^^<xs:boolean> .\n");
+        assert_canonical("_:a <name> \"Alice\"^^<xs:string> .\n");
+        assert_canonical("_:a <pass> \"secret\"^^<xs:password> .\n");
+
+        assert_eq!(object("_:a <age> \"26\"^^<xs:int> ."), int_val(26));
+        assert_eq!(object("_:a <alive> \"t\"^^<xs:boolean> ."), bool_val(true));
+        assert_eq!(object("_:a <name> \"Alice\"^^<xs:string> ."), str_val("Alice".to_string()));
+    }
+
+    #[test]
+    fn reads_full_xml_schema_iris() {
+        let text = "_:a <age> \"26\"^^<http://www.w3.org/2001/XMLSchema#int> .\n";
+        assert_eq!(round_trip(text), "_:a <age> \"26\"^^<xs:int> .\n");
+
+        let text = "_:a <weight> \"1e3\"^^<http://www.w3.org/2001/XMLSchema#double> .\n";
+        assert_eq!(round_trip(text), "_:a <weight> \"1000.0\"^^<xs:float> .\n");
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn round_trips_dates() {
+        assert_canonical("_:a <born> \"2006-01-02T15:04:05Z\"^^<xs:dateTime> .\n");
+        assert_canonical("_:a <born> \"2006-01-02T15:04:05.123Z\"^^<xs:dateTime> .\n");
+        assert_canonical("_:a <born> \"2006-01-02\"^^<xs:date> .\n");
+
+        let text = "_:a <born> \"2006-01-02T17:04:05+02:00\"^^<xs:dateTime> .\n";
+        assert_eq!(round_trip(text), "_:a <born> \"2006-01-02T15:04:05Z\"^^<xs:dateTime> .\n");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_geojson() {
+        round_trip("_:a <loc> \"{\\\"type\\\":\\\"Point\\\",\\\"coordinates\\\":[1.5,-2.0]}\"^^<geo:geojson> .\n");
+    }
+
+    #[test]
+    fn round_trips_facets() {
+        assert_canonical("_:a <friend> _:b (since=\"2006\", close=true, weight=-1.5, rank=-3, count=7) .\n");
+        assert_canonical("_:a <name> \"Alice\"@en (verified=false) .\n");
+        assert_canonical("_:a <name> \"Alice\" (quote=\"say \\\"hi\\\"\") .\n");
+
+        assert_eq!(round_trip("_:a <friend> _:b ( rank = +3 , weight=.5 ) .\n"), "_:a <friend> _:b (rank=3, weight=0.5) .\n");
+        assert_eq!(round_trip("_:a <friend> _:b () .\n"), "_:a <friend> _:b .\n");
+
+        let nquads = parse_nquads("_:a <friend> _:b (rank=-3, weight=-1.5) .").unwrap();
+        assert_eq!(nquads[0].facets[0].value_as::<i64>().unwrap(), -3);
+        assert_eq!(nquads[0].facets[1].value_as::<f64>().unwrap(), -1.5);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn round_trips_datetime_facets() {
+        assert_canonical("_:a <friend> _:b (since=2006-01-02T15:04:05Z) .\n");
+        assert_eq!(round_trip("_:a <friend> _:b (since=2006-01-02) .\n"), "_:a <friend> _:b (since=2006-01-02T00:00:00Z) .\n");
+    }
+
+    #[test]
+    fn round_trips_uids_and_blank_nodes() {
+        assert_canonical("<0x1a> <friend> <0x2b> .\n");
+        assert_canonical("_:alice <friend> _:bob .\n");
+        assert_canonical("_:alice.smith <friend> _:bob-2 .\n");
+        assert_eq!(round_trip("_:alice <friend> _:bob.\n"), "_:alice <friend> _:bob .\n");
+
+        let nquads = parse_nquads("<0x1a> <friend> <0x2b> .").unwrap();
+        assert_eq!(nquads[0].subject, "0x1a");
+        assert_eq!(nquads[0].object_id, "0x2b");
+    }
+
+    #[test]
+    fn round_trips_labels() {
+        assert_canonical("_:a <name> \"Alice\" <graph> .\n");
+        assert_canonical("_:a <friend> _:b _:graph (close=true) .\n");
+
+        let nquads = parse_nquads("_:a <name> \"Alice\" <graph> .").unwrap();
+        assert_eq!(nquads[0].label, "graph");
+    }
+
+    #[test]
+    fn round_trips_wildcards() {
+        assert_canonical("<0x1> <name> * .\n");
+        assert_canonical("<0x1> * * .\n");
+
+        let nquads = parse_nquads("<0x1> * * .").unwrap();
+        assert_eq!(nquads[0].predicate, STAR_ALL);
+        assert_eq!(nquads[0].object_value.as_ref().unwrap().get_default_val(), STAR_ALL);
+    }
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        let text = "\n# people\n  _:a <name> \"Alice\" . # trailing comment\n\t\n";
+        assert_eq!(round_trip(text), "_:a <name> \"Alice\" .\n");
+    }
+
+    #[test]
+    fn reports_error_locations() {
+        assert_eq!(parse_error("<0x1> <name> \"abc ."), (1, 20, "unterminated string".to_string()));
+        assert_eq!(
+            parse_error("_:a <p> \"x\" .\n<0x1> <name> \"abc\"^^<xs:foo> ."),
+            (2, 21, "unsupported literal type <xs:foo>".to_string())
+        );
+        assert_eq!(parse_error("<a b> <p> <c> ."), (1, 3, "invalid character ' ' in IRI".to_string()));
+        assert_eq!(parse_error("_: <p> \"x\" ."), (1, 3, "empty blank node label".to_string()));
+        assert_eq!(parse_error("_:a <p> \"\\q\" ."), (1, 12, "invalid escape sequence in string".to_string()));
+        assert_eq!(parse_error("_:a <p> \"\\u00\" ."), (1, 12, "invalid unicode escape \"00\\\" \"".to_string()));
+        assert_eq!(parse_error("_:a <p> \"x\"@ ."), (1, 13, "empty language tag".to_string()));
+        assert_eq!(parse_error("_:a <p> \"26x\"^^<xs:int> ."), (1, 16, "invalid integer \"26x\"".to_string()));
+        assert_eq!(parse_error("_:a <p> \"x\" (w=abc) ."), (1, 16, "could not parse the facet value \"abc\"".to_string()));
+        assert_eq!(parse_error("_:a <p> \"x\" (w=1 v=2) ."), (1, 18, "expected ',' or ')' after facet".to_string()));
+        assert_eq!(parse_error("_:a <p> \"x\""), (1, 12, "expected '.'".to_string()));
+        assert_eq!(parse_error("_:a <p> \"x\" . extra"), (1, 15, "unexpected characters after the end of the NQuad".to_string()));
+        assert_eq!(parse_error("\"x\" <p> _:a ."), (1, 1, "expected subject as an IRI or a blank node".to_string()));
+    }
+
+    #[test]
+    fn writes_uid_values_as_object_ids() {
+        let nquad = NQuadBuilder::new(Uid::new(0x1)).predicate("friend").object_value(Uid::new(0x1a)).build();
+        let text = format_nquads(std::slice::from_ref(&nquad)).unwrap();
+        assert_eq!(text, "<0x1> <friend> <0x1a> .\n");
+
+        let parsed = parse_nquads(&text).unwrap();
+        assert_eq!(parsed[0].object_id, "0x1a");
+        assert!(parsed[0].object_value.is_none());
+        assert_eq!(parsed[0].object_uid(), nquad.object_uid());
+        assert_eq!(format_nquads(&parsed).unwrap(), text);
+    }
+
+    #[test]
+    fn refuses_to_write_invalid_blank_nodes() {
+        for label in &["_:a b", "_:a>", "_:", "_:a.", "_:_:a"] {
+            let nquad = NQuadBuilder::blank("b").predicate("friend").object_blank(label).build();
+            assert_eq!(format_nquad(&nquad), Err(RdfError::Unsupported(format!("the blank node {:?}", label))));
+
+            let nquad = NQuadBuilder::blank(label).predicate("friend").object_blank("b").build();
+            assert!(format_nquad(&nquad).is_err());
+
+            let nquad = NQuadBuilder::blank("b").predicate("name").object_value(api::Value::from("x")).label(*label).build();
+            assert!(format_nquad(&nquad).is_err());
+        }
+
+        assert_canonical("_:a.b <friend> _:b-2 _:c_3 .\n");
+    }
+
+    #[test]
+    fn refuses_to_write_unsupported_values() {
+        let mut value = api::Value::new();
+        value.set_bytes_val(vec![1, 2]);
+        let mut nquad = api::NQuad::new();
+        nquad.subject = "_:a".to_string();
+        nquad.predicate = "p".to_string();
+        nquad.set_object_value(value);
+        assert_eq!(format_nquad(&nquad), Err(RdfError::Unsupported("a bytes value".to_string())));
+
+        nquad.clear_object_value();
+        assert_eq!(format_nquad(&nquad), Err(RdfError::Unsupported("a NQuad without object".to_string())));
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::Duration;
+use rand::prelude::*;
+
+use crate::errors::DgraphError;
+
+/// Controls how `Dgraph::transaction_with_policy` retries transactions that
+/// were aborted because of a conflict.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of times the transaction is run, including the first attempt.
+    pub max_attempts: u32,
+    /// Delay before the first retry. It is doubled on every subsequent retry.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two attempts.
+    pub max_backoff: Duration,
+    /// Randomizes each delay between half and the full backoff, so that
+    /// conflicting clients do not retry in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay to wait before running the transaction again after
+    /// the given (1-based) attempt failed with the error, or `None` if it must
+    /// not be retried. Only aborted transactions are retried.
+    pub(crate) fn retry_after(&self, attempt: u32, err: &DgraphError) -> Option<Duration> {
+        match err {
+            DgraphError::TxnAborted if attempt < self.max_attempts => Some(self.backoff(attempt)),
+            _ => None,
+        }
+    }
+
+    /// Returns the delay to wait after the given (1-based) failed attempt.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::max_value());
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff));
+
+        if !self.jitter {
+            return backoff;
+        }
+
+        let millis = backoff.as_millis() as u64;
+        if millis < 2 {
+            return backoff;
+        }
+
+        Duration::from_millis(thread_rng().gen_range(millis / 2, millis + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: u32, jitter: bool) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+            jitter,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = policy(10, false);
+        let delays: Vec<_> = (1..=6).map(|attempt| policy.backoff(attempt).as_millis()).collect();
+        assert_eq!(delays, vec![10, 20, 40, 80, 100, 100]);
+    }
+
+    #[test]
+    fn backoff_does_not_overflow() {
+        let policy = policy(10, false);
+        assert_eq!(policy.backoff(0), Duration::from_millis(10));
+        assert_eq!(policy.backoff(33), Duration::from_millis(100));
+        assert_eq!(policy.backoff(u32::max_value()), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn jitter_stays_between_half_and_full_backoff() {
+        let jittered = policy(10, true);
+        for attempt in 1..=6 {
+            let full = policy(10, false).backoff(attempt);
+            for _ in 0..100 {
+                let delay = jittered.backoff(attempt);
+                assert!(delay >= full / 2 && delay <= full, "{:?} out of bounds for {:?}", delay, full);
+            }
+        }
+    }
+
+    #[test]
+    fn jitter_keeps_tiny_backoffs() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..policy(10, true)
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn retries_aborted_transactions_until_the_last_attempt() {
+        let policy = policy(3, false);
+        assert_eq!(policy.retry_after(1, &DgraphError::TxnAborted), Some(Duration::from_millis(10)));
+        assert_eq!(policy.retry_after(2, &DgraphError::TxnAborted), Some(Duration::from_millis(20)));
+        assert_eq!(policy.retry_after(3, &DgraphError::TxnAborted), None);
+    }
+
+    #[test]
+    fn does_not_retry_with_zero_or_one_attempt() {
+        assert_eq!(policy(0, false).retry_after(1, &DgraphError::TxnAborted), None);
+        assert_eq!(policy(1, false).retry_after(1, &DgraphError::TxnAborted), None);
+    }
+
+    #[test]
+    fn does_not_retry_other_errors() {
+        let policy = policy(3, false);
+        let errors = vec![
+            DgraphError::TxnFinished,
+            DgraphError::TxnReadOnly,
+            DgraphError::MissingTxnContext,
+            DgraphError::NoClients,
+            DgraphError::StartTsMismatch { expected: 1, got: 2 },
+            DgraphError::InvalidUid("0x".to_string()),
+        ];
+        for err in errors.iter() {
+            assert_eq!(policy.retry_after(1, err), None, "{} must not be retried", err);
+        }
+    }
+}
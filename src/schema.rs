@@ -0,0 +1,1258 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use failure::Fail;
+
+use crate::client::Dgraph;
+use crate::errors::DgraphError;
+use crate::protos::api;
+
+/// Errors returned when building, parsing or writing a schema.
+#[derive(Debug, Fail, PartialEq)]
+pub enum SchemaError {
+    #[fail(display = "Invalid schema for predicate {}: {}", predicate, message)]
+    Invalid { predicate: String, message: String },
+    #[fail(display = "Invalid schema at line {}, column {}: {}", line, column, message)]
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+/// Type of the values of a predicate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScalarType {
+    Default,
+    Int,
+    Float,
+    String,
+    Bool,
+    DateTime,
+    Geo,
+    Password,
+    Uid,
+}
+
+impl ScalarType {
+    /// Name of the type in schema text, like `datetime`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ScalarType::Default => "default",
+            ScalarType::Int => "int",
+            ScalarType::Float => "float",
+            ScalarType::String => "string",
+            ScalarType::Bool => "bool",
+            ScalarType::DateTime => "datetime",
+            ScalarType::Geo => "geo",
+            ScalarType::Password => "password",
+            ScalarType::Uid => "uid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ScalarType> {
+        match name {
+            "default" => Some(ScalarType::Default),
+            "int" => Some(ScalarType::Int),
+            "float" => Some(ScalarType::Float),
+            "string" => Some(ScalarType::String),
+            "bool" => Some(ScalarType::Bool),
+            "datetime" => Some(ScalarType::DateTime),
+            "geo" => Some(ScalarType::Geo),
+            "password" => Some(ScalarType::Password),
+            "uid" => Some(ScalarType::Uid),
+            _ => None,
+        }
+    }
+}
+
+/// Tokenizer of an `@index` directive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Tokenizer {
+    Exact,
+    Hash,
+    Term,
+    Fulltext,
+    Trigram,
+    Year,
+    Month,
+    Day,
+    Hour,
+    Geo,
+    Int,
+    Float,
+    Bool,
+}
+
+impl Tokenizer {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tokenizer::Exact => "exact",
+            Tokenizer::Hash => "hash",
+            Tokenizer::Term => "term",
+            Tokenizer::Fulltext => "fulltext",
+            Tokenizer::Trigram => "trigram",
+            Tokenizer::Year => "year",
+            Tokenizer::Month => "month",
+            Tokenizer::Day => "day",
+            Tokenizer::Hour => "hour",
+            Tokenizer::Geo => "geo",
+            Tokenizer::Int => "int",
+            Tokenizer::Float => "float",
+            Tokenizer::Bool => "bool",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Tokenizer> {
+        match name {
+            "exact" => Some(Tokenizer::Exact),
+            "hash" => Some(Tokenizer::Hash),
+            "term" => Some(Tokenizer::Term),
+            "fulltext" => Some(Tokenizer::Fulltext),
+            "trigram" => Some(Tokenizer::Trigram),
+            "year" => Some(Tokenizer::Year),
+            "month" => Some(Tokenizer::Month),
+            "day" => Some(Tokenizer::Day),
+            "hour" => Some(Tokenizer::Hour),
+            "geo" => Some(Tokenizer::Geo),
+            "int" => Some(Tokenizer::Int),
+            "float" => Some(Tokenizer::Float),
+            "bool" => Some(Tokenizer::Bool),
+            _ => None,
+        }
+    }
+
+    /// Type of the values the tokenizer applies to.
+    pub fn value_type(self) -> ScalarType {
+        match self {
+            Tokenizer::Exact | Tokenizer::Hash | Tokenizer::Term | Tokenizer::Fulltext | Tokenizer::Trigram => {
+                ScalarType::String
+            }
+            Tokenizer::Year | Tokenizer::Month | Tokenizer::Day | Tokenizer::Hour => ScalarType::DateTime,
+            Tokenizer::Geo => ScalarType::Geo,
+            Tokenizer::Int => ScalarType::Int,
+            Tokenizer::Float => ScalarType::Float,
+            Tokenizer::Bool => ScalarType::Bool,
+        }
+    }
+
+    /// Sortable tokenizers back `orderasc`, `le`, `ge`, ... and only one of
+    /// them is allowed per predicate.
+    pub fn is_sortable(self) -> bool {
+        match self {
+            Tokenizer::Exact
+            | Tokenizer::Year
+            | Tokenizer::Month
+            | Tokenizer::Day
+            | Tokenizer::Hour
+            | Tokenizer::Int
+            | Tokenizer::Float => true,
+            _ => false,
+        }
+    }
+}
+
+/// Builder of the schema of a single predicate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Predicate {
+    node: api::SchemaNode,
+}
+
+impl Predicate {
+    pub fn new(name: &str, scalar_type: ScalarType) -> Predicate {
+        let mut node = api::SchemaNode::new();
+        node.predicate = name.to_string();
+        node.field_type = scalar_type.name().to_string();
+        Predicate { node }
+    }
+
+    /// Makes the predicate hold a list of values, like `[uid]` or `[string]`.
+    pub fn list(mut self) -> Self {
+        self.node.list = true;
+        self
+    }
+
+    /// Indexes the values with the given tokenizers, `@index(exact, term)`.
+    pub fn index(mut self, tokenizers: &[Tokenizer]) -> Self {
+        self.node.index = true;
+        self.node.tokenizer = tokenizers.iter().map(|tokenizer| tokenizer.name().to_string()).collect();
+        self
+    }
+
+    /// Maintains the reverse edges of an `uid` predicate.
+    pub fn reverse(mut self) -> Self {
+        self.node.reverse = true;
+        self
+    }
+
+    /// Maintains the count of values, for `count(predicate)` in functions.
+    pub fn count(mut self) -> Self {
+        self.node.count = true;
+        self
+    }
+
+    /// Checks conflicts on the index in concurrent transactions.
+    pub fn upsert(mut self) -> Self {
+        self.node.upsert = true;
+        self
+    }
+
+    /// Allows values in several languages.
+    pub fn lang(mut self) -> Self {
+        self.node.lang = true;
+        self
+    }
+
+    /// Validates the predicate and returns it as a schema node.
+    pub fn build(self) -> Result<api::SchemaNode, SchemaError> {
+        validate_node(&self.node)?;
+        Ok(self.node)
+    }
+}
+
+/// Builder of a schema document, for `Operation.schema`.
+///
+/// ```
+/// use dgraph::schema::{Predicate, ScalarType, Schema, Tokenizer};
+///
+/// let schema = Schema::new()
+///     .predicate(Predicate::new("name", ScalarType::String).index(&[Tokenizer::Exact, Tokenizer::Term]).lang())
+///     .predicate(Predicate::new("friend", ScalarType::Uid).list().reverse().count())
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(schema, "name: string @index(exact, term) @lang .\nfriend: [uid] @reverse @count .\n");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    predicates: Vec<Predicate>,
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    pub fn predicate(mut self, predicate: Predicate) -> Self {
+        self.predicates.push(predicate);
+        self
+    }
+
+    /// Validates the predicates and returns their schema nodes.
+    pub fn nodes(&self) -> Result<Vec<api::SchemaNode>, SchemaError> {
+        let mut names = HashSet::new();
+        let mut nodes = Vec::with_capacity(self.predicates.len());
+
+        for predicate in self.predicates.iter() {
+            if !names.insert(predicate.node.predicate.as_str()) {
+                return Err(invalid(&predicate.node, "declared more than once"));
+            }
+            nodes.push(predicate.clone().build()?);
+        }
+
+        Ok(nodes)
+    }
+
+    /// Validates the predicates and writes the schema text, one predicate per line.
+    pub fn build(&self) -> Result<String, SchemaError> {
+        format_schema(&self.nodes()?)
+    }
+
+    /// Builds the operation altering the schema, for `Dgraph::alter`.
+    pub fn operation(&self) -> Result<api::Operation, SchemaError> {
+        Ok(api::Operation {
+            schema: self.build()?,
+            ..Default::default()
+        })
+    }
+}
+
+fn invalid(node: &api::SchemaNode, message: impl Into<String>) -> SchemaError {
+    SchemaError::Invalid {
+        predicate: node.predicate.clone(),
+        message: message.into(),
+    }
+}
+
+/// Characters of predicate names that can be written without `<>`.
+fn is_name_char(c: char) -> bool {
+    !c.is_whitespace() && !"@:<>()[]{},\"\\#".contains(c)
+}
+
+/// Checks that the directives of the node are consistent with its type.
+pub(crate) fn validate_node(node: &api::SchemaNode) -> Result<(), SchemaError> {
+    if node.predicate.is_empty() || node.predicate.chars().any(|c| c.is_whitespace() || c == '<' || c == '>') {
+        return Err(invalid(node, "invalid predicate name"));
+    }
+
+    validate_directives(node)?;
+    if node.upsert && !node.index {
+        return Err(invalid(node, "@upsert needs an @index"));
+    }
+
+    Ok(())
+}
+
+/// Checks the directives of the node against its type, whatever their order.
+fn validate_directives(node: &api::SchemaNode) -> Result<(), SchemaError> {
+    let scalar_type = match ScalarType::from_name(&node.field_type) {
+        Some(scalar_type) => scalar_type,
+        None => return Err(invalid(node, format!("unknown type {:?}", node.field_type))),
+    };
+
+    if node.index {
+        if node.tokenizer.is_empty() {
+            return Err(invalid(node, "@index needs at least one tokenizer"));
+        }
+
+        let mut seen = HashSet::new();
+        let mut sortable = None;
+        for name in node.tokenizer.iter() {
+            let tokenizer = match Tokenizer::from_name(name) {
+                Some(tokenizer) => tokenizer,
+                None => return Err(invalid(node, format!("unknown tokenizer {:?}", name))),
+            };
+            if !seen.insert(tokenizer) {
+                return Err(invalid(node, format!("tokenizer {} is repeated", name)));
+            }
+            if tokenizer.value_type() != scalar_type {
+                return Err(invalid(
+                    node,
+                    format!("tokenizer {} cannot index values of type {}", name, scalar_type.name()),
+                ));
+            }
+            if tokenizer.is_sortable() {
+                if let Some(other) = sortable.replace(tokenizer) {
+                    return Err(invalid(
+                        node,
+                        format!("tokenizers {} and {} are both sortable", other.name(), name),
+                    ));
+                }
+            }
+        }
+    } else if !node.tokenizer.is_empty() {
+        return Err(invalid(node, "tokenizers given without @index"));
+    }
+
+    if node.reverse && scalar_type != ScalarType::Uid {
+        return Err(invalid(node, "@reverse is only allowed on uid predicates"));
+    }
+    if node.lang && scalar_type != ScalarType::String {
+        return Err(invalid(node, "@lang is only allowed on string predicates"));
+    }
+
+    Ok(())
+}
+
+/// Writes the node as a line of schema text, without the trailing newline.
+pub(crate) fn write_node(out: &mut String, node: &api::SchemaNode) {
+    if node.predicate.chars().all(is_name_char) {
+        out.push_str(&node.predicate);
+    } else {
+        out.push('<');
+        out.push_str(&node.predicate);
+        out.push('>');
+    }
+    out.push_str(": ");
+    if node.list {
+        out.push('[');
+        out.push_str(&node.field_type);
+        out.push(']');
+    } else {
+        out.push_str(&node.field_type);
+    }
+
+    if node.index {
+        out.push_str(" @index(");
+        out.push_str(&node.tokenizer.join(", "));
+        out.push(')');
+    }
+    if node.reverse {
+        out.push_str(" @reverse");
+    }
+    if node.count {
+        out.push_str(" @count");
+    }
+    if node.upsert {
+        out.push_str(" @upsert");
+    }
+    if node.lang {
+        out.push_str(" @lang");
+    }
+
+    out.push_str(" .");
+}
+
+impl Dgraph {
+    /// Fetches the schema of the given predicates, or of all of them if none
+    /// are given, with a `schema {}` query in a read-only transaction.
+    ///
+    /// Servers answering in the JSON payload rather than in `Response.schema`
+    /// need the `serde` feature, without which this fails with
+    /// `DgraphError::JsonSchemaUnsupported`.
+    pub fn schema(&self, predicates: &[&str]) -> Result<Vec<api::SchemaNode>, DgraphError> {
+        let res = self.new_readonly_txn()?.query(schema_query(predicates))?;
+        schema_nodes(res)
+    }
+
+    /// Async counterpart of `schema`.
+    pub async fn schema_async(&self, predicates: &[&str]) -> Result<Vec<api::SchemaNode>, DgraphError> {
+        let res = self.new_async_readonly_txn()?.query(schema_query(predicates)).await?;
+        schema_nodes(res)
+    }
+}
+
+impl Dgraph {
+    /// Compares the expected schema with the one of the cluster.
+    pub fn schema_diff(&self, expected: &[api::SchemaNode]) -> Result<SchemaDiff, DgraphError> {
+        Ok(diff_schema(expected, &self.schema(&[])?))
+    }
+
+    /// Fails with `DgraphError::SchemaDrift` if the schema of the cluster
+    /// differs from the expected one, for checks at startup or deploy time.
+    pub fn check_schema(&self, expected: &[api::SchemaNode]) -> Result<(), DgraphError> {
+        let diff = self.schema_diff(expected)?;
+        if diff.is_empty() {
+            Ok(())
+        } else {
+            Err(DgraphError::SchemaDrift(diff))
+        }
+    }
+
+    /// Async counterpart of `schema_diff`.
+    pub async fn schema_diff_async(&self, expected: &[api::SchemaNode]) -> Result<SchemaDiff, DgraphError> {
+        Ok(diff_schema(expected, &self.schema_async(&[]).await?))
+    }
+
+    /// Async counterpart of `check_schema`.
+    pub async fn check_schema_async(&self, expected: &[api::SchemaNode]) -> Result<(), DgraphError> {
+        let diff = self.schema_diff_async(expected).await?;
+        if diff.is_empty() {
+            Ok(())
+        } else {
+            Err(DgraphError::SchemaDrift(diff))
+        }
+    }
+}
+
+fn schema_query(predicates: &[&str]) -> String {
+    if predicates.is_empty() {
+        return "schema {}".to_string();
+    }
+
+    let predicates: Vec<_> = predicates
+        .iter()
+        .map(|predicate| {
+            if predicate.chars().all(is_name_char) {
+                predicate.to_string()
+            } else {
+                format!("<{}>", predicate)
+            }
+        })
+        .collect();
+    format!("schema(pred: [{}]) {{}}", predicates.join(", "))
+}
+
+/// Older servers answer schema queries in `Response.schema`, newer ones in the
+/// JSON payload, which is only read with the `serde` feature.
+fn schema_nodes(mut res: api::Response) -> Result<Vec<api::SchemaNode>, DgraphError> {
+    if !res.schema.is_empty() || res.json.is_empty() {
+        return Ok(res.take_schema().into_vec());
+    }
+
+    json_schema_nodes(&res.json)
+}
+
+#[cfg(feature = "serde")]
+fn json_schema_nodes(json: &[u8]) -> Result<Vec<api::SchemaNode>, DgraphError> {
+    use serde_json::Value;
+
+    let json: Value = serde_json::from_slice(json).map_err(|err| DgraphError::JsonDecode {
+        path: "$".to_string(),
+        err,
+    })?;
+
+    // A payload without the list of nodes is not mistaken for an empty schema.
+    let entries = json.get("schema").cloned().unwrap_or(Value::Null);
+    let entries: Vec<Value> = serde_json::from_value(entries).map_err(|err| DgraphError::JsonDecode {
+        path: "$.schema".to_string(),
+        err,
+    })?;
+
+    let flag = |entry: &Value, key: &str| entry.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let text = |entry: &Value, key: &str| entry.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let mut node = api::SchemaNode::new();
+            node.predicate = text(entry, "predicate");
+            node.field_type = text(entry, "type");
+            node.index = flag(entry, "index");
+            node.tokenizer = entry
+                .get("tokenizer")
+                .and_then(Value::as_array)
+                .map(|tokenizers| {
+                    tokenizers
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            node.reverse = flag(entry, "reverse");
+            node.count = flag(entry, "count");
+            node.list = flag(entry, "list");
+            node.upsert = flag(entry, "upsert");
+            node.lang = flag(entry, "lang");
+            node
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "serde"))]
+fn json_schema_nodes(_json: &[u8]) -> Result<Vec<api::SchemaNode>, DgraphError> {
+    Err(DgraphError::JsonSchemaUnsupported)
+}
+
+/// Difference between an expected schema and the actual one, as computed by
+/// `diff_schema`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchemaDiff {
+    /// Predicates expected but missing from the actual schema.
+    pub added: Vec<api::SchemaNode>,
+    /// Predicates of the actual schema that are not expected.
+    pub removed: Vec<api::SchemaNode>,
+    pub changed: Vec<ChangedPredicate>,
+}
+
+/// Predicate found in both schemas, with different definitions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangedPredicate {
+    pub predicate: String,
+    pub changes: Vec<PredicateChange>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PredicateChange {
+    /// Type of the values, like `string` or `[uid]`.
+    Type { expected: String, actual: String },
+    /// Tokenizers expected but missing, and unexpected ones.
+    Tokenizers { missing: Vec<String>, unexpected: Vec<String> },
+    /// The directive, like `@reverse`, is expected or not.
+    Directive { name: &'static str, expected: bool },
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Lists the differences one predicate per line, prefixed with `+` for added,
+/// `-` for removed and `~` for changed predicates.
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        for node in self.added.iter() {
+            out.push_str("+ ");
+            write_node(&mut out, node);
+            out.push('\n');
+        }
+        for node in self.removed.iter() {
+            out.push_str("- ");
+            write_node(&mut out, node);
+            out.push('\n');
+        }
+        for changed in self.changed.iter() {
+            let changes: Vec<_> = changed.changes.iter().map(PredicateChange::to_string).collect();
+            out.push_str(&format!("~ {}: {}\n", changed.predicate, changes.join(", ")));
+        }
+        f.write_str(out.trim_end())
+    }
+}
+
+impl fmt::Display for PredicateChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PredicateChange::Type { expected, actual } => write!(f, "type is {}, expected {}", actual, expected),
+            PredicateChange::Tokenizers { missing, unexpected } => {
+                let mut parts = Vec::new();
+                if !missing.is_empty() {
+                    parts.push(format!("missing tokenizers {}", missing.join(", ")));
+                }
+                if !unexpected.is_empty() {
+                    parts.push(format!("unexpected tokenizers {}", unexpected.join(", ")));
+                }
+                f.write_str(&parts.join(", "))
+            }
+            PredicateChange::Directive { name, expected: true } => write!(f, "{} is missing", name),
+            PredicateChange::Directive { name, expected: false } => write!(f, "{} is unexpected", name),
+        }
+    }
+}
+
+/// Predicates maintained by the server itself, left out of diffs.
+fn is_internal(predicate: &str) -> bool {
+    predicate.starts_with("dgraph.") || predicate == "_predicate_"
+}
+
+fn type_text(node: &api::SchemaNode) -> String {
+    if node.list {
+        format!("[{}]", node.field_type)
+    } else {
+        node.field_type.clone()
+    }
+}
+
+fn predicate_changes(expected: &api::SchemaNode, actual: &api::SchemaNode) -> Vec<PredicateChange> {
+    let mut changes = Vec::new();
+
+    if type_text(expected) != type_text(actual) {
+        changes.push(PredicateChange::Type {
+            expected: type_text(expected),
+            actual: type_text(actual),
+        });
+    }
+
+    // Tokenizers are compared as sets, an index without them being no index.
+    let expected_tokenizers: BTreeSet<_> = expected.tokenizer.iter().filter(|_| expected.index).collect();
+    let actual_tokenizers: BTreeSet<_> = actual.tokenizer.iter().filter(|_| actual.index).collect();
+    if expected_tokenizers != actual_tokenizers {
+        changes.push(PredicateChange::Tokenizers {
+            missing: expected_tokenizers.difference(&actual_tokenizers).map(|t| t.to_string()).collect(),
+            unexpected: actual_tokenizers.difference(&expected_tokenizers).map(|t| t.to_string()).collect(),
+        });
+    }
+
+    let directives = [
+        ("@reverse", expected.reverse, actual.reverse),
+        ("@count", expected.count, actual.count),
+        ("@upsert", expected.upsert, actual.upsert),
+        ("@lang", expected.lang, actual.lang),
+    ];
+    for &(name, expected, actual) in directives.iter() {
+        if expected != actual {
+            changes.push(PredicateChange::Directive { name, expected });
+        }
+    }
+
+    changes
+}
+
+/// Compares an expected schema, from `parse_schema` or `Schema::nodes`, with
+/// the actual one, usually from `Dgraph::schema`. Internal `dgraph.*`
+/// predicates of the actual schema are ignored.
+///
+/// ```
+/// use dgraph::schema::{diff_schema, parse_schema};
+///
+/// let expected = parse_schema("name: string @index(exact, term) .\nfriend: [uid] @reverse .").unwrap();
+/// let actual = parse_schema("name: string @index(exact) @lang .\nage: int .").unwrap();
+///
+/// let diff = diff_schema(&expected, &actual);
+/// assert_eq!(diff.to_string(), "+ friend: [uid] @reverse .\n- age: int .\n~ name: missing tokenizers term, @lang is unexpected");
+/// ```
+pub fn diff_schema(expected: &[api::SchemaNode], actual: &[api::SchemaNode]) -> SchemaDiff {
+    let actual_nodes: HashMap<_, _> = actual.iter().map(|node| (node.predicate.as_str(), node)).collect();
+    let expected_names: HashSet<_> = expected.iter().map(|node| node.predicate.as_str()).collect();
+
+    let mut diff = SchemaDiff::default();
+    for node in expected {
+        match actual_nodes.get(node.predicate.as_str()) {
+            Some(actual) => {
+                let changes = predicate_changes(node, actual);
+                if !changes.is_empty() {
+                    diff.changed.push(ChangedPredicate {
+                        predicate: node.predicate.clone(),
+                        changes,
+                    });
+                }
+            }
+            None => diff.added.push(node.clone()),
+        }
+    }
+
+    diff.removed = actual
+        .iter()
+        .filter(|node| !expected_names.contains(node.predicate.as_str()) && !is_internal(&node.predicate))
+        .cloned()
+        .collect();
+
+    diff
+}
+
+/// Parses a schema document, as accepted by `Operation.schema`, into schema
+/// nodes. Each predicate is validated as by `Schema::build`, and errors are
+/// reported with the line and column they were found at.
+///
+/// ```
+/// let text = "name: string @index(exact, term) @lang .\n<schema:friend>: [uid] @reverse .\n";
+///
+/// let nodes = dgraph::schema::parse_schema(text).unwrap();
+/// assert_eq!(nodes[1].predicate, "schema:friend");
+/// assert!(nodes[1].list && nodes[1].reverse);
+/// assert_eq!(dgraph::schema::format_schema(&nodes).unwrap(), text);
+///
+/// let err = dgraph::schema::parse_schema("age: int\n  @index(term) .").unwrap_err();
+/// assert_eq!(err.to_string(), "Invalid schema at line 2, column 10: tokenizer term cannot index values of type int");
+/// ```
+pub fn parse_schema(text: &str) -> Result<Vec<api::SchemaNode>, SchemaError> {
+    let mut parser = SchemaParser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+
+    let mut names = HashSet::new();
+    let mut nodes = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            break;
+        }
+
+        let start = parser.pos;
+        let node = parser.statement()?;
+        if !names.insert(node.predicate.clone()) {
+            parser.pos = start;
+            return parser.error(format!("predicate {} is declared more than once", node.predicate));
+        }
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+/// Validates the schema nodes and writes them as schema text, one per line.
+pub fn format_schema(nodes: &[api::SchemaNode]) -> Result<String, SchemaError> {
+    let mut out = String::new();
+    for node in nodes {
+        validate_node(node)?;
+        write_node(&mut out, node);
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+struct SchemaParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl SchemaParser {
+    fn statement(&mut self) -> Result<api::SchemaNode, SchemaError> {
+        let mut node = api::SchemaNode::new();
+
+        let start = self.pos;
+        node.predicate = self.predicate()?;
+        if node.predicate == "type" && self.chars[start] != '<' && self.at_type_definition() {
+            self.pos = start;
+            return self.error("type definitions are not supported");
+        }
+        self.skip_whitespace();
+        self.expect(':')?;
+        self.skip_whitespace();
+
+        let type_start = self.pos;
+        node.list = self.eat('[');
+        self.skip_whitespace();
+        node.field_type = self.word();
+        if ScalarType::from_name(&node.field_type).is_none() {
+            self.pos = type_start;
+            return self.error(format!("unknown type {:?}", node.field_type));
+        }
+        if node.list {
+            self.skip_whitespace();
+            self.expect(']')?;
+        }
+
+        // Directives are checked against the type as they are parsed, so that
+        // errors point at the directive at fault.
+        let mut upsert_start = None;
+        loop {
+            self.skip_whitespace();
+            let directive_start = self.pos;
+            if !self.eat('@') {
+                break;
+            }
+
+            match self.word().as_str() {
+                "index" => {
+                    if node.index {
+                        self.pos = directive_start;
+                        return self.error("@index is repeated");
+                    }
+                    node.index = true;
+                    node.tokenizer = self.tokenizers(&node.field_type)?.into();
+                }
+                "reverse" => node.reverse = true,
+                "count" => node.count = true,
+                "upsert" => {
+                    node.upsert = true;
+                    upsert_start = Some(directive_start);
+                }
+                "lang" => node.lang = true,
+                directive => {
+                    self.pos = directive_start;
+                    return self.error(format!("unknown directive @{}", directive));
+                }
+            }
+
+            if let Err(SchemaError::Invalid { message, .. }) = validate_directives(&node) {
+                self.pos = directive_start;
+                return self.error(message);
+            }
+        }
+
+        if let (Some(upsert_start), false) = (upsert_start, node.index) {
+            self.pos = upsert_start;
+            return self.error("@upsert needs an @index");
+        }
+        if let Err(SchemaError::Invalid { message, .. }) = validate_node(&node) {
+            self.pos = start;
+            return self.error(message);
+        }
+
+        self.expect('.')?;
+        Ok(node)
+    }
+
+    fn predicate(&mut self) -> Result<String, SchemaError> {
+        if self.eat('<') {
+            let start = self.pos;
+            while let Some(c) = self.next() {
+                if c == '>' {
+                    let name: String = self.chars[start..self.pos - 1].iter().collect();
+                    if name.is_empty() {
+                        return self.error("empty predicate name");
+                    }
+                    return Ok(name);
+                }
+                if c.is_whitespace() || c == '<' {
+                    self.pos -= 1;
+                    return self.error(format!("invalid character {:?} in predicate name", c));
+                }
+            }
+            return self.error("unterminated predicate name");
+        }
+
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if is_name_char(c) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if self.pos == start {
+            return self.error("expected a predicate name");
+        }
+
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// Tells whether the `type` keyword just read starts a type definition,
+    /// that is whether it is followed by a name and `{`.
+    fn at_type_definition(&mut self) -> bool {
+        let pos = self.pos;
+        self.skip_whitespace();
+        let named = self.predicate().is_ok();
+        self.skip_whitespace();
+        let definition = named && self.peek() == Some('{');
+        self.pos = pos;
+        definition
+    }
+
+    fn tokenizers(&mut self, field_type: &str) -> Result<Vec<String>, SchemaError> {
+        self.skip_whitespace();
+        self.expect('(')?;
+
+        let mut tokenizers = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if tokenizers.is_empty() && self.eat(')') {
+                return Ok(tokenizers);
+            }
+
+            let start = self.pos;
+            let tokenizer = self.word();
+            match Tokenizer::from_name(&tokenizer) {
+                Some(known) if known.value_type().name() != field_type => {
+                    self.pos = start;
+                    return self.error(format!(
+                        "tokenizer {} cannot index values of type {}",
+                        tokenizer, field_type
+                    ));
+                }
+                Some(_) => tokenizers.push(tokenizer),
+                None => {
+                    self.pos = start;
+                    return self.error(format!("unknown tokenizer {:?}", tokenizer));
+                }
+            }
+
+            self.skip_whitespace();
+            if self.eat(',') {
+                continue;
+            }
+            if self.eat(')') {
+                return Ok(tokenizers);
+            }
+            return self.error("expected ',' or ')' after tokenizer");
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Reports an error at the current position, counting lines and columns
+    /// from 1.
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SchemaError> {
+        let before = &self.chars[..self.pos.min(self.chars.len())];
+        let line_start = before.iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1);
+        Err(SchemaError::Parse {
+            line: before.iter().filter(|&&c| c == '\n').count() + 1,
+            column: before.len() - line_start + 1,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SchemaError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(format!("expected {:?}", c))
+        }
+    }
+
+    /// Skips whitespace, including newlines, and `#` comments.
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while let Some(c) = self.next() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if c.is_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str) -> (usize, usize, String) {
+        match parse_schema(text) {
+            Err(SchemaError::Parse { line, column, message }) => (line, column, message),
+            other => panic!("expected a parse error for {:?}, got {:?}", text, other),
+        }
+    }
+
+    #[test]
+    fn parses_predicates_named_type() {
+        for text in &["type: string .", "type : string .", "type\n: string .", "<type>: string ."] {
+            let nodes = parse_schema(text).unwrap();
+            assert_eq!(nodes[0].predicate, "type");
+            assert_eq!(nodes[0].field_type, "string");
+        }
+    }
+
+    #[test]
+    fn rejects_type_definitions() {
+        let expected = (1, 1, "type definitions are not supported".to_string());
+        assert_eq!(parse_error("type Person {\n  name\n}"), expected);
+        assert_eq!(parse_error("type Person{}"), expected);
+        assert_eq!(parse_error("name: string .\ntype <Person> {}"), (2, 1, expected.2));
+    }
+
+    #[test]
+    fn reports_error_locations() {
+        assert_eq!(parse_error("name string ."), (1, 6, "expected ':'".to_string()));
+        assert_eq!(parse_error("name: strin ."), (1, 7, "unknown type \"strin\"".to_string()));
+        assert_eq!(parse_error("name: [string ."), (1, 15, "expected ']'".to_string()));
+        assert_eq!(parse_error("name: string @index(exact) @foo ."), (1, 28, "unknown directive @foo".to_string()));
+        assert_eq!(parse_error("name: string @index(exact, trigam) ."), (1, 28, "unknown tokenizer \"trigam\"".to_string()));
+        assert_eq!(parse_error("name: string @index(exact term) ."), (1, 27, "expected ',' or ')' after tokenizer".to_string()));
+        assert_eq!(parse_error("name: string @index(exact) @index(term) ."), (1, 28, "@index is repeated".to_string()));
+        assert_eq!(parse_error("name: string @reverse ."), (1, 14, "@reverse is only allowed on uid predicates".to_string()));
+        assert_eq!(parse_error("age: int @upsert ."), (1, 10, "@upsert needs an @index".to_string()));
+        assert_eq!(parse_error("name: string"), (1, 13, "expected '.'".to_string()));
+        assert_eq!(parse_error("# names\n<na me>: string ."), (2, 4, "invalid character ' ' in predicate name".to_string()));
+        assert_eq!(parse_error("<>: string ."), (1, 3, "empty predicate name".to_string()));
+        assert_eq!(parse_error("<name: string ."), (1, 7, "invalid character ' ' in predicate name".to_string()));
+        assert_eq!(parse_error("a: int .\nb: int .\n  a: string ."), (3, 3, "predicate a is declared more than once".to_string()));
+    }
+
+    #[test]
+    fn parser_and_builder_agree_on_names() {
+        // Names with characters that need `<>` are accepted by both, and the
+        // builder writes them back in a form the parser reads.
+        let node = Predicate::new("a@b", ScalarType::String).build().unwrap();
+        assert_eq!(format_schema(std::slice::from_ref(&node)).unwrap(), "<a@b>: string .\n");
+        assert_eq!(parse_schema("<a@b>: string .").unwrap(), vec![node]);
+
+        // Names with whitespace are rejected by both.
+        assert_eq!(
+            Predicate::new("a b", ScalarType::String).build(),
+            Err(SchemaError::Invalid {
+                predicate: "a b".to_string(),
+                message: "invalid predicate name".to_string(),
+            })
+        );
+        assert_eq!(parse_error("<a b>: string .").0, 1);
+        assert!(Predicate::new("", ScalarType::String).build().is_err());
+        assert!(parse_schema("<>: string .").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn rejects_json_payloads_without_schema_nodes() {
+        for json in &[&b"{}"[..], br#"{"schema": {"predicate": "name"}}"#, br#"{"data": []}"#, b"[]"] {
+            let mut res = api::Response::new();
+            res.json = json.to_vec();
+            match schema_nodes(res) {
+                Err(DgraphError::JsonDecode { path, .. }) => assert_eq!(path, "$.schema"),
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+
+        let mut res = api::Response::new();
+        res.json = br#"{"schema": []}"#.to_vec();
+        assert_eq!(schema_nodes(res).unwrap(), Vec::new());
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn reads_schema_from_the_response_field_only() {
+        let mut res = api::Response::new();
+        res.schema = vec![Predicate::new("age", ScalarType::Int).build().unwrap()].into();
+        res.json = b"{}".to_vec();
+        assert_eq!(schema_nodes(res).unwrap()[0].predicate, "age");
+
+        let mut res = api::Response::new();
+        res.json = br#"{"schema": [{"predicate": "age", "type": "int"}]}"#.to_vec();
+        match schema_nodes(res) {
+            Err(DgraphError::JsonSchemaUnsupported) => (),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reads_schema_from_either_response_field() {
+        let mut res = api::Response::new();
+        res.json = br#"{"schema":[{"predicate":"name","type":"string","index":true,"tokenizer":["exact"],"lang":true},{"predicate":"friend","type":"uid","list":true}]}"#.to_vec();
+        let nodes = schema_nodes(res).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].predicate, "name");
+        assert!(nodes[0].index && nodes[0].lang);
+        assert_eq!(nodes[0].tokenizer.to_vec(), vec!["exact".to_string()]);
+        assert!(nodes[1].list && !nodes[1].index);
+
+        let mut res = api::Response::new();
+        res.schema = vec![Predicate::new("age", ScalarType::Int).build().unwrap()].into();
+        assert_eq!(schema_nodes(res).unwrap()[0].predicate, "age");
+
+        let mut res = api::Response::new();
+        res.json = b"{\"schema\": [".to_vec();
+        assert!(schema_nodes(res).is_err());
+    }
+
+    fn invalid_message(predicate: Predicate) -> String {
+        match predicate.build() {
+            Err(SchemaError::Invalid { message, .. }) => message,
+            res => panic!("expected an invalid schema, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn accepts_one_sortable_tokenizer_among_others() {
+        let node = Predicate::new("name", ScalarType::String)
+            .index(&[Tokenizer::Term, Tokenizer::Exact, Tokenizer::Trigram])
+            .build()
+            .unwrap();
+        assert_eq!(node.tokenizer.to_vec(), vec!["term", "exact", "trigram"]);
+
+        let message = invalid_message(Predicate::new("born", ScalarType::DateTime).index(&[Tokenizer::Year, Tokenizer::Day]));
+        assert_eq!(message, "tokenizers year and day are both sortable");
+    }
+
+    #[test]
+    fn rejects_upsert_without_index() {
+        let message = invalid_message(Predicate::new("email", ScalarType::String).upsert());
+        assert_eq!(message, "@upsert needs an @index");
+
+        assert!(Predicate::new("email", ScalarType::String).index(&[Tokenizer::Exact]).upsert().build().is_ok());
+    }
+
+    #[test]
+    fn rejects_tokenizers_of_another_type() {
+        let message = invalid_message(Predicate::new("age", ScalarType::Int).index(&[Tokenizer::Term]));
+        assert_eq!(message, "tokenizer term cannot index values of type int");
+
+        let message = invalid_message(Predicate::new("name", ScalarType::String).index(&[Tokenizer::Exact, Tokenizer::Int]));
+        assert_eq!(message, "tokenizer int cannot index values of type string");
+    }
+
+    #[test]
+    fn rejects_inconsistent_directives() {
+        let message = invalid_message(Predicate::new("name", ScalarType::String).index(&[]));
+        assert_eq!(message, "@index needs at least one tokenizer");
+
+        let message = invalid_message(Predicate::new("name", ScalarType::String).index(&[Tokenizer::Term, Tokenizer::Term]));
+        assert_eq!(message, "tokenizer term is repeated");
+
+        let message = invalid_message(Predicate::new("name", ScalarType::String).reverse());
+        assert_eq!(message, "@reverse is only allowed on uid predicates");
+
+        let message = invalid_message(Predicate::new("age", ScalarType::Int).lang());
+        assert_eq!(message, "@lang is only allowed on string predicates");
+    }
+
+    #[test]
+    fn rejects_duplicate_predicates() {
+        let schema = Schema::new()
+            .predicate(Predicate::new("name", ScalarType::String))
+            .predicate(Predicate::new("age", ScalarType::Int))
+            .predicate(Predicate::new("name", ScalarType::String).lang());
+        assert_eq!(
+            schema.build(),
+            Err(SchemaError::Invalid {
+                predicate: "name".to_string(),
+                message: "declared more than once".to_string(),
+            })
+        );
+    }
+
+    fn diff(expected: &str, actual: &str) -> SchemaDiff {
+        diff_schema(&parse_schema(expected).unwrap(), &parse_schema(actual).unwrap())
+    }
+
+    fn changes(expected: &str, actual: &str) -> Vec<PredicateChange> {
+        let diff = diff(expected, actual);
+        assert!(diff.added.is_empty() && diff.removed.is_empty(), "{}", diff);
+        match diff.changed.as_slice() {
+            [] => Vec::new(),
+            [changed] => changed.changes.clone(),
+            _ => panic!("more than one predicate changed: {}", diff),
+        }
+    }
+
+    fn tokenizers(missing: &[&str], unexpected: &[&str]) -> PredicateChange {
+        PredicateChange::Tokenizers {
+            missing: names(missing),
+            unexpected: names(unexpected),
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn compares_tokenizers_as_sets() {
+        assert!(diff("name: string @index(exact, term) .", "name: string @index(term, exact) .").is_empty());
+        assert_eq!(
+            changes("name: string @index(exact, term) .", "name: string @index(trigram, exact) ."),
+            vec![tokenizers(&["term"], &["trigram"])]
+        );
+    }
+
+    #[test]
+    fn reports_indexes_added_or_removed() {
+        assert_eq!(
+            changes("name: string @index(exact, term) .", "name: string ."),
+            vec![tokenizers(&["exact", "term"], &[])]
+        );
+        assert_eq!(changes("name: string .", "name: string @index(hash) ."), vec![tokenizers(&[], &["hash"])]);
+    }
+
+    #[test]
+    fn reports_directives_added_or_removed() {
+        let directive = |name, expected| PredicateChange::Directive { name, expected };
+
+        assert_eq!(changes("friend: [uid] @reverse .", "friend: [uid] ."), vec![directive("@reverse", true)]);
+        assert_eq!(changes("friend: [uid] .", "friend: [uid] @count ."), vec![directive("@count", false)]);
+        assert_eq!(
+            changes("email: string @index(exact) @upsert .", "email: string @index(exact) ."),
+            vec![directive("@upsert", true)]
+        );
+        assert_eq!(changes("name: string .", "name: string @lang ."), vec![directive("@lang", false)]);
+        assert_eq!(
+            changes("friend: [uid] @reverse @count .", "friend: [uid] @count @reverse ."),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn reports_type_and_list_changes() {
+        let change = |expected: &str, actual: &str| PredicateChange::Type {
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        };
+
+        assert_eq!(changes("friend: [uid] .", "friend: uid ."), vec![change("[uid]", "uid")]);
+        assert_eq!(changes("tags: string .", "tags: [string] ."), vec![change("string", "[string]")]);
+        assert_eq!(changes("age: int .", "age: float ."), vec![change("int", "float")]);
+    }
+
+    #[test]
+    fn reports_predicates_on_one_side() {
+        let diff = diff("name: string .\nage: int .", "name: string .\nfriend: [uid] .");
+        assert_eq!(diff.added.iter().map(|node| node.predicate.as_str()).collect::<Vec<_>>(), vec!["age"]);
+        assert_eq!(diff.removed.iter().map(|node| node.predicate.as_str()).collect::<Vec<_>>(), vec!["friend"]);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.to_string(), "+ age: int .\n- friend: [uid] .");
+    }
+
+    #[test]
+    fn ignores_internal_predicates() {
+        let actual = "name: string .\ndgraph.type: [string] @index(exact) .\n_predicate_: [string] .\ndgraph.password: password .";
+        assert!(diff("name: string .", actual).is_empty());
+
+        // Internal predicates that are expected are still compared.
+        assert_eq!(diff("dgraph.type: [string] .", actual).changed.len(), 1);
+    }
+
+    #[test]
+    fn round_trips_parsed_schema() {
+        let text = "name: string @index(exact, term) @lang .\n<schema:friend>: [uid] @reverse @count .\nemail: string @index(hash) @upsert .\n";
+        assert_eq!(format_schema(&parse_schema(text).unwrap()).unwrap(), text);
+    }
+}
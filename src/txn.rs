@@ -1,71 +1,88 @@
 use std::collections::HashMap;
-use failure::{bail, Error};
 
+use crate::client::Dgraph;
+use crate::errors::DgraphError;
 use crate::protos::api_grpc;
 use crate::protos::api;
+use crate::query::Vars;
 
-pub struct Txn<'a> {
+pub struct Txn {
     pub(super) context: api::TxnContext,
     pub(super) finished: bool,
     pub(super) read_only: bool,
     pub(super) mutated: bool,
-    pub(super) client: &'a api_grpc::DgraphClient,
+    pub(super) dgraph: Dgraph,
+    pub(super) client: api_grpc::DgraphClient,
 }
 
+// Transactions own their handles, so they can be moved to other threads.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Txn>();
+};
+
 /// Call Txn::discard() once txn goes out of scope.
 /// This is safe to do so, and is possible a no-op
-impl Drop for Txn<'_> {
+impl Drop for Txn {
     fn drop(&mut self) {
-        println!("Calling discard");
         let _ = self.discard();
     }
 }
 
-impl Txn<'_> {
-    pub fn query(&mut self, query: impl Into<String>) -> Result<api::Response, Error> {
+impl Txn {
+    pub fn query(&mut self, query: impl Into<String>) -> Result<api::Response, DgraphError> {
         self.query_with_vars(query, HashMap::new())
     }
 
-    pub fn query_with_vars(&mut self, query: impl Into<String>, vars: HashMap<String, String>) -> Result<api::Response, Error> {
+    pub fn query_with_vars(&mut self, query: impl Into<String>, vars: HashMap<String, String>) -> Result<api::Response, DgraphError> {
         if self.finished {
-            bail!("Transaction has already been committed or discarded");
+            return Err(DgraphError::TxnFinished);
         }
 
-        let res = self.client.query(&api::Request 
+        let request = api::Request 
         { 
             query: query.into(), 
             vars, 
             ..Default::default()
-        })?;
+        };
+        let res = self.dgraph.with_jwt_refresh(|opt| self.client.query_opt(&request, opt))?;
 
         let txn = match res.txn.as_ref() {
             Some(txn) => txn,
-            None => bail!("Got empty Txn response back from query")
+            None => return Err(DgraphError::MissingTxnContext)
         };
 
-        self.merge_context(txn)?;
+        merge_context(&mut self.context, txn)?;
 
         Ok(res)
     }
 
-    pub fn mutate(&mut self, mut mu: api::Mutation) -> Result<api::Assigned, Error> {
+    /// Runs the query with typed variables, failing before reaching the server
+    /// if the query uses a variable that is not declared or has no value.
+    pub fn query_with_typed_vars(&mut self, query: impl Into<String>, vars: &Vars) -> Result<api::Response, DgraphError> {
+        let query = query.into();
+        vars.check(&query)?;
+        self.query_with_vars(query, vars.to_map())
+    }
+
+    pub fn mutate(&mut self, mut mu: api::Mutation) -> Result<api::Assigned, DgraphError> {
 
         match (self.finished, self.read_only) {
-            (true, _) => bail!("Txn is finished"),
-            (_, true) => bail!("Txn is read only"),
+            (true, _) => return Err(DgraphError::TxnFinished),
+            (_, true) => return Err(DgraphError::TxnReadOnly),
             _ => ()
         }
 
         self.mutated = true;
         mu.start_ts = self.context.start_ts;
         let commit_now = mu.commit_now;
-        let mu_res = self.client.mutate(&mu);
+        let mu_res = self.dgraph.with_jwt_refresh(|opt| self.client.mutate_opt(&mu, opt));
 
         let mu_res = match mu_res {
             Ok(mu_res) => mu_res,
             Err(e) => {
                 let _ = self.discard();
-                bail!(e);
+                return Err(e);
             }
         };
 
@@ -76,31 +93,31 @@ impl Txn<'_> {
         {
             let context = match mu_res.context.as_ref() {
                 Some(context) => context,
-                None => bail!("Missing Txn context on mutation response")
+                None => return Err(DgraphError::MissingTxnContext)
             };
 
-            self.merge_context(context)?;
+            merge_context(&mut self.context, context)?;
         }
 
         Ok(mu_res)
     }
 
-    pub fn commit(mut self) -> Result<(), Error> {
+    pub fn commit(mut self) -> Result<(), DgraphError> {
         match (self.finished, self.read_only) {
-            (true, _) => bail!("Txn is finished"),
-            (_, true) => bail!("Txn is read only"),
+            (true, _) => return Err(DgraphError::TxnFinished),
+            (_, true) => return Err(DgraphError::TxnReadOnly),
             _ => ()
         }
 
         self.commit_or_abort()
     }
 
-    pub fn discard(&mut self) -> Result<(), Error> {
+    pub fn discard(&mut self) -> Result<(), DgraphError> {
         self.context.aborted = true;
         self.commit_or_abort()
     }
 
-    fn commit_or_abort(&mut self) -> Result<(), Error> {
+    fn commit_or_abort(&mut self) -> Result<(), DgraphError> {
         if self.finished {
             return Ok(())
         }
@@ -110,28 +127,87 @@ impl Txn<'_> {
             return Ok(())
         }
 
-        self.client.commit_or_abort(&self.context)?;
+        let res = self.dgraph.with_jwt_refresh(|opt| self.client.commit_or_abort_opt(&self.context, opt))?;
 
-        Ok(())
+        check_commit(&res, &self.context)
     }
+}
 
-    fn merge_context(&mut self, src: &api::TxnContext) -> Result<(), Error> {
-        if self.context.start_ts == 0 {
-            self.context.start_ts = src.start_ts;
-        }
+/// Checks the context returned by a commit. The server marks the transaction
+/// as aborted when it lost a conflict, which is only expected when it was
+/// discarded on purpose.
+pub(crate) fn check_commit(res: &api::TxnContext, context: &api::TxnContext) -> Result<(), DgraphError> {
+    if res.aborted && !context.aborted {
+        return Err(DgraphError::TxnAborted);
+    }
 
-        if self.context.start_ts != src.start_ts {
-            bail!("self.context.start_ts != src.start_ts")
-        }
+    Ok(())
+}
+
+/// Merges the transaction context returned by the server into the local one.
+pub(crate) fn merge_context(dst: &mut api::TxnContext, src: &api::TxnContext) -> Result<(), DgraphError> {
+    if dst.start_ts == 0 {
+        dst.start_ts = src.start_ts;
+    }
+
+    if dst.start_ts != src.start_ts {
+        return Err(DgraphError::StartTsMismatch {
+            expected: dst.start_ts,
+            got: src.start_ts,
+        });
+    }
 
-        for key in src.keys.iter() {
-            self.context.keys.push(key.clone());
+    for key in src.keys.iter() {
+        dst.keys.push(key.clone());
+    }
+
+    for pred in src.preds.iter() {
+        dst.preds.push(pred.clone());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(aborted: bool) -> api::TxnContext {
+        api::TxnContext {
+            start_ts: 5,
+            aborted,
+            ..Default::default()
         }
+    }
 
-        for pred in src.preds.iter() {
-            self.context.preds.push(pred.clone());
+    #[test]
+    fn aborted_commit_is_a_conflict() {
+        match check_commit(&context(true), &context(false)) {
+            Err(DgraphError::TxnAborted) => (),
+            res => panic!("unexpected result {:?}", res),
         }
+    }
 
-        Ok(())
+    #[test]
+    fn aborted_discard_is_not_an_error() {
+        assert!(check_commit(&context(true), &context(true)).is_ok());
+        assert!(check_commit(&context(false), &context(false)).is_ok());
+    }
+
+    #[test]
+    fn merges_contexts_with_the_same_start_ts() {
+        let mut dst = api::TxnContext::new();
+        let mut src = context(false);
+        src.keys = vec!["a".to_string()].into();
+        merge_context(&mut dst, &src).unwrap();
+        merge_context(&mut dst, &src).unwrap();
+        assert_eq!(dst.start_ts, 5);
+        assert_eq!(dst.keys.len(), 2);
+
+        src.start_ts = 6;
+        match merge_context(&mut dst, &src) {
+            Err(DgraphError::StartTsMismatch { expected: 5, got: 6 }) => (),
+            res => panic!("unexpected result {:?}", res),
+        }
     }
 }
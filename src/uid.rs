@@ -0,0 +1,226 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::DgraphError;
+use crate::protos::api;
+use crate::value::ToDgraphValue;
+
+/// Uid of a Dgraph node. It is formatted as a hex string, like `0x1a`, which is
+/// how uids appear in JSON, in `Assigned.uids` and in NQuad subjects and objects.
+/// The server never assigns uid 0, so parsing and deserializing reject it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uid(u64);
+
+impl Uid {
+    pub fn new(uid: u64) -> Uid {
+        Uid(uid)
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the uid held by `Value::uid_val`, if that is the variant set.
+    pub fn from_value(value: &api::Value) -> Option<Uid> {
+        if value.has_uid_val() {
+            Some(Uid(value.get_uid_val()))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+/// Parses hex uids prefixed with `0x`, as well as plain decimal numbers.
+impl FromStr for Uid {
+    type Err = DgraphError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (digits, radix) = if s.starts_with("0x") || s.starts_with("0X") {
+            (&s[2..], 16)
+        } else {
+            (s, 10)
+        };
+
+        // from_str_radix accepts a leading sign, which is not part of a uid.
+        if !digits.chars().all(|c| c.is_digit(radix)) {
+            return Err(DgraphError::InvalidUid(s.to_string()));
+        }
+
+        match u64::from_str_radix(digits, radix) {
+            Ok(0) | Err(_) => Err(DgraphError::InvalidUid(s.to_string())),
+            Ok(uid) => Ok(Uid(uid)),
+        }
+    }
+}
+
+impl From<u64> for Uid {
+    fn from(uid: u64) -> Self {
+        Uid(uid)
+    }
+}
+
+impl From<Uid> for u64 {
+    fn from(uid: Uid) -> Self {
+        uid.0
+    }
+}
+
+impl From<Uid> for api::Value {
+    fn from(uid: Uid) -> Self {
+        uid.to_dgraph_value()
+    }
+}
+
+impl api::NQuad {
+    /// Returns the subject as a uid, or `None` if it is a blank node.
+    pub fn subject_uid(&self) -> Option<Uid> {
+        self.subject.parse().ok()
+    }
+
+    /// Returns the object as a uid, read either from `object_id` or from a
+    /// `uid_val` object value.
+    pub fn object_uid(&self) -> Option<Uid> {
+        if !self.object_id.is_empty() {
+            return self.object_id.parse().ok();
+        }
+        self.object_value.as_ref().and_then(Uid::from_value)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::fmt;
+    use serde::de::{self, Deserialize, Deserializer, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::Uid;
+    use crate::errors::DgraphError;
+
+    impl Serialize for Uid {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    struct UidVisitor;
+
+    impl<'de> Visitor<'de> for UidVisitor {
+        type Value = Uid;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a uid like \"0x1a\"")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Uid, E> {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Uid, E> {
+            if v == 0 {
+                return Err(E::custom(DgraphError::InvalidUid(v.to_string())));
+            }
+            Ok(Uid(v))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Uid {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Uid, D::Error> {
+            deserializer.deserialize_any(UidVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Option<u64> {
+        s.parse::<Uid>().ok().map(Uid::as_u64)
+    }
+
+    #[test]
+    fn parses_hex_and_decimal_uids() {
+        assert_eq!(parse("0x1a"), Some(26));
+        assert_eq!(parse("0X1A"), Some(26));
+        assert_eq!(parse("26"), Some(26));
+        assert_eq!(parse("0xffffffffffffffff"), Some(u64::max_value()));
+        assert_eq!(parse("18446744073709551615"), Some(u64::max_value()));
+    }
+
+    #[test]
+    fn rejects_empty_and_zero_uids() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("0x"), None);
+        assert_eq!(parse("0x0"), None);
+        assert_eq!(parse("0"), None);
+    }
+
+    #[test]
+    fn rejects_signs() {
+        assert_eq!(parse("0x+1"), None);
+        assert_eq!(parse("0x-1"), None);
+        assert_eq!(parse("+5"), None);
+        assert_eq!(parse("-5"), None);
+    }
+
+    #[test]
+    fn rejects_overflow_and_garbage() {
+        assert_eq!(parse("0x10000000000000000"), None);
+        assert_eq!(parse("18446744073709551616"), None);
+        assert_eq!(parse("0x1g"), None);
+        assert_eq!(parse(" 1"), None);
+        assert_eq!(parse("_:alice"), None);
+    }
+
+    #[test]
+    fn formats_as_hex() {
+        assert_eq!(Uid::new(26).to_string(), "0x1a");
+        assert_eq!(parse(&Uid::new(u64::max_value()).to_string()), Some(u64::max_value()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_the_uid_field() {
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Node {
+            uid: Uid,
+        }
+
+        let node = Node { uid: Uid::new(26) };
+        let json = serde_json::to_string(&node).unwrap();
+        assert_eq!(json, r#"{"uid":"0x1a"}"#);
+        assert_eq!(serde_json::from_str::<Node>(&json).unwrap(), node);
+        assert!(serde_json::from_str::<Node>(r#"{"uid":"0x+1"}"#).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn rejects_zero_in_every_form() {
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, Deserialize)]
+        struct Node {
+            uid: Uid,
+        }
+
+        assert_eq!(serde_json::from_str::<Node>(r#"{"uid":26}"#).unwrap().uid, Uid::new(26));
+        for json in &[r#"{"uid":0}"#, r#"{"uid":"0"}"#, r#"{"uid":"0x0"}"#] {
+            let err = serde_json::from_str::<Node>(json).unwrap_err();
+            assert!(err.to_string().starts_with("Invalid uid: 0"), "{}", err);
+        }
+    }
+}
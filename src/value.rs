@@ -0,0 +1,307 @@
+use std::convert::TryFrom;
+
+use crate::errors::DgraphError;
+use crate::protos::api;
+use crate::protos::api::Value_oneof_val::*;
+use crate::uid::Uid;
+
+/// Conversion of Rust values into `api::Value`, the object of a NQuad.
+pub trait ToDgraphValue {
+    fn to_dgraph_value(&self) -> api::Value;
+}
+
+/// Conversion of an `api::Value` back into a Rust value. A value holding
+/// another variant than the one expected is reported as an error.
+pub trait FromDgraphValue: Sized {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError>;
+}
+
+/// Name of the variant held by the value, as used in error messages.
+fn variant_name(value: &api::Value) -> &'static str {
+    match value.val {
+        Some(default_val(_)) => "default_val",
+        Some(bytes_val(_)) => "bytes_val",
+        Some(int_val(_)) => "int_val",
+        Some(bool_val(_)) => "bool_val",
+        Some(str_val(_)) => "str_val",
+        Some(double_val(_)) => "double_val",
+        Some(geo_val(_)) => "geo_val",
+        Some(date_val(_)) => "date_val",
+        Some(datetime_val(_)) => "datetime_val",
+        Some(password_val(_)) => "password_val",
+        Some(uid_val(_)) => "uid_val",
+        None => "no value",
+    }
+}
+
+pub(crate) fn mismatch(expected: &'static str, value: &api::Value) -> DgraphError {
+    DgraphError::ValueTypeMismatch {
+        expected,
+        found: variant_name(value),
+    }
+}
+
+macro_rules! int_value {
+    ($($ty:ty),*) => {
+        $(
+            impl ToDgraphValue for $ty {
+                fn to_dgraph_value(&self) -> api::Value {
+                    let mut value = api::Value::new();
+                    value.set_int_val(i64::from(*self));
+                    value
+                }
+            }
+
+            impl FromDgraphValue for $ty {
+                fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+                    match value.val {
+                        Some(int_val(int)) => {
+                            if int < <$ty>::min_value() as i64 || int > <$ty>::max_value() as i64 {
+                                return Err(DgraphError::IntOutOfRange {
+                                    value: i128::from(int),
+                                    target: stringify!($ty),
+                                });
+                            }
+                            Ok(int as $ty)
+                        }
+                        _ => Err(mismatch("int_val", value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_value!(i8, i16, i32, i64, u8, u16, u32);
+
+/// Dgraph integers are signed, so a `u64` is only written if it fits in an
+/// `i64`. `u64` has no `ToDgraphValue` impl for that reason.
+impl TryFrom<u64> for api::Value {
+    type Error = DgraphError;
+
+    fn try_from(uint: u64) -> Result<Self, DgraphError> {
+        if uint > i64::max_value() as u64 {
+            return Err(DgraphError::IntOutOfRange {
+                value: i128::from(uint),
+                target: "i64",
+            });
+        }
+        Ok((uint as i64).to_dgraph_value())
+    }
+}
+
+impl FromDgraphValue for u64 {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(int_val(int)) if int < 0 => Err(DgraphError::IntOutOfRange {
+                value: i128::from(int),
+                target: "u64",
+            }),
+            Some(int_val(int)) => Ok(int as u64),
+            _ => Err(mismatch("int_val", value)),
+        }
+    }
+}
+
+impl ToDgraphValue for f64 {
+    fn to_dgraph_value(&self) -> api::Value {
+        let mut value = api::Value::new();
+        value.set_double_val(*self);
+        value
+    }
+}
+
+impl FromDgraphValue for f64 {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(double_val(float)) => Ok(float),
+            _ => Err(mismatch("double_val", value)),
+        }
+    }
+}
+
+impl ToDgraphValue for f32 {
+    fn to_dgraph_value(&self) -> api::Value {
+        f64::from(*self).to_dgraph_value()
+    }
+}
+
+/// Only doubles holding an `f32` exactly, like the ones written from an `f32`,
+/// are read, other ones fail with `DgraphError::FloatPrecisionLoss`.
+impl FromDgraphValue for f32 {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        let float = f64::from_dgraph_value(value)?;
+        if float.is_nan() || f64::from(float as f32) == float {
+            Ok(float as f32)
+        } else {
+            Err(DgraphError::FloatPrecisionLoss {
+                value: float,
+                target: "f32",
+            })
+        }
+    }
+}
+
+impl ToDgraphValue for bool {
+    fn to_dgraph_value(&self) -> api::Value {
+        let mut value = api::Value::new();
+        value.set_bool_val(*self);
+        value
+    }
+}
+
+impl FromDgraphValue for bool {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(bool_val(b)) => Ok(b),
+            _ => Err(mismatch("bool_val", value)),
+        }
+    }
+}
+
+impl ToDgraphValue for str {
+    fn to_dgraph_value(&self) -> api::Value {
+        let mut value = api::Value::new();
+        value.set_str_val(self.to_string());
+        value
+    }
+}
+
+impl ToDgraphValue for String {
+    fn to_dgraph_value(&self) -> api::Value {
+        self.as_str().to_dgraph_value()
+    }
+}
+
+/// Strings are read from both `str_val` and the untyped `default_val`.
+impl FromDgraphValue for String {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(str_val(ref text)) | Some(default_val(ref text)) => Ok(text.clone()),
+            _ => Err(mismatch("str_val", value)),
+        }
+    }
+}
+
+impl ToDgraphValue for Vec<u8> {
+    fn to_dgraph_value(&self) -> api::Value {
+        let mut value = api::Value::new();
+        value.set_bytes_val(self.clone());
+        value
+    }
+}
+
+impl FromDgraphValue for Vec<u8> {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(bytes_val(ref bytes)) => Ok(bytes.clone()),
+            _ => Err(mismatch("bytes_val", value)),
+        }
+    }
+}
+
+impl ToDgraphValue for Uid {
+    fn to_dgraph_value(&self) -> api::Value {
+        let mut value = api::Value::new();
+        value.set_uid_val(self.as_u64());
+        value
+    }
+}
+
+impl FromDgraphValue for Uid {
+    fn from_dgraph_value(value: &api::Value) -> Result<Self, DgraphError> {
+        match value.val {
+            Some(uid_val(uid)) => Ok(Uid::new(uid)),
+            _ => Err(mismatch("uid_val", value)),
+        }
+    }
+}
+
+impl ToDgraphValue for api::Value {
+    fn to_dgraph_value(&self) -> api::Value {
+        self.clone()
+    }
+}
+
+macro_rules! from_value {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for api::Value {
+                fn from(value: $ty) -> Self {
+                    value.to_dgraph_value()
+                }
+            }
+        )*
+    };
+}
+
+from_value!(i8, i16, i32, i64, u8, u16, u32, f32, f64, bool, String, &str, Vec<u8>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(int: i64) -> api::Value {
+        int.to_dgraph_value()
+    }
+
+    fn double(float: f64) -> api::Value {
+        float.to_dgraph_value()
+    }
+
+    #[test]
+    fn range_checks_integers() {
+        assert_eq!(u8::from_dgraph_value(&int(255)).unwrap(), 255);
+        assert_eq!(i8::from_dgraph_value(&int(-128)).unwrap(), -128);
+        match u8::from_dgraph_value(&int(256)) {
+            Err(DgraphError::IntOutOfRange { value: 256, target: "u8" }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+        match u32::from_dgraph_value(&int(-1)) {
+            Err(DgraphError::IntOutOfRange { value: -1, target: "u32" }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[test]
+    fn converts_u64_within_i64_range() {
+        let max = i64::max_value() as u64;
+        let value = api::Value::try_from(max).unwrap();
+        assert_eq!(value.get_int_val(), i64::max_value());
+        assert_eq!(u64::from_dgraph_value(&value).unwrap(), max);
+
+        match api::Value::try_from(max + 1) {
+            Err(DgraphError::IntOutOfRange { value, target: "i64" }) => assert_eq!(value, i128::from(max + 1)),
+            res => panic!("unexpected {:?}", res),
+        }
+        match u64::from_dgraph_value(&int(-1)) {
+            Err(DgraphError::IntOutOfRange { value: -1, target: "u64" }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[test]
+    fn reads_f32_without_losing_precision() {
+        assert_eq!(f32::from_dgraph_value(&1.5f32.to_dgraph_value()).unwrap(), 1.5);
+        assert_eq!(f32::from_dgraph_value(&0.1f32.to_dgraph_value()).unwrap(), 0.1);
+        assert_eq!(f32::from_dgraph_value(&double(std::f64::INFINITY)).unwrap(), std::f32::INFINITY);
+        assert!(f32::from_dgraph_value(&double(std::f64::NAN)).unwrap().is_nan());
+
+        for &float in [0.1, 1e39, std::f64::consts::PI].iter() {
+            match f32::from_dgraph_value(&double(float)) {
+                Err(DgraphError::FloatPrecisionLoss { target: "f32", .. }) => (),
+                res => panic!("unexpected {:?} for {}", res, float),
+            }
+        }
+    }
+
+    #[test]
+    fn reports_variant_mismatches() {
+        match bool::from_dgraph_value(&int(1)) {
+            Err(DgraphError::ValueTypeMismatch { expected: "bool_val", found: "int_val" }) => (),
+            res => panic!("unexpected {:?}", res),
+        }
+        assert_eq!(String::from_dgraph_value(&"Alice".to_dgraph_value()).unwrap(), "Alice");
+        assert_eq!(Uid::from_dgraph_value(&Uid::new(26).to_dgraph_value()).unwrap(), Uid::new(26));
+    }
+}